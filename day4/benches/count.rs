@@ -0,0 +1,54 @@
+//! Compares three ways of counting part 2 style passwords in `0..10^n`.
+//!
+//! Run with `cargo bench`. Serial checks every number on one thread,
+//! parallel checks every number across all of them, and combinatorial
+//! counts the non-decreasing digit sequences without looking at numbers at
+//! all. Checking every number on one thread is skipped past 10^8, where it
+//! already takes a while.
+
+use std::time::{Duration, Instant};
+
+use day4::count::{brute_force, brute_force_parallel, count};
+use day4::rules::{Policy, Rule};
+
+
+/// The largest range, as a power of ten, checked on one thread.
+const SERIAL_MAX_POWER: u32 = 8;
+
+
+fn time<F: FnMut() -> u64>(name: &str, iterations: u32, mut f: F) -> (Duration, u64) {
+    let start = Instant::now();
+    let mut result = 0;
+    for _ in 0..iterations {
+        result = std::hint::black_box(f());
+    }
+    let elapsed = start.elapsed() / iterations;
+    println!("{:<40} {:>12?}", name, elapsed);
+    (elapsed, result)
+}
+
+
+fn main() {
+    // no length rule, so the number of matches keeps growing with the range
+    let policy = Policy::new().with(Rule::NonDecreasing).with(Rule::HasExactRun(2));
+    println!("running on {} threads", rayon::current_num_threads());
+
+    for power in 6..=9 {
+        let end = 10u64.pow(power) - 1;
+        println!("0..10^{}", power);
+        let mut results = Vec::new();
+        if power <= SERIAL_MAX_POWER {
+            results.push(time("  serial", 1, || brute_force(&policy, 0, end)));
+        } else {
+            println!("  {:<38} {:>12}", "serial", "skipped");
+        }
+        results.push(time("  parallel", 1, || brute_force_parallel(&policy, 0, end)));
+        results.push(time("  combinatorial", 100, || count(&policy, 0, end).unwrap()));
+
+        let (_, expected) = results[0];
+        assert!(results.iter().all(|&(_, result)| result == expected), "the methods disagree over 0..10^{}", power);
+        if let [.., (parallel, _), (combinatorial, _)] = results[..] {
+            println!("  {} matches, combinatorial is {:.0}x faster than parallel", expected, parallel.as_secs_f64() / combinatorial.as_secs_f64());
+        }
+    }
+}
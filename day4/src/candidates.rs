@@ -0,0 +1,301 @@
+//! Walking the valid passwords in a range, in order.
+//!
+//! With a non-decreasing policy most numbers can be skipped without looking at
+//! them: after 138241 the next number that doesn't decrease is 138888, found by
+//! copying the digit before the first decrease over everything after it.
+//! Going backwards, the last one at or before 138241 is 137999. Policies
+//! without `Rule::NonDecreasing` are walked one number at a time.
+//!
+//! When `count` can handle the policy it also answers `count`, `nth` and
+//! `size_hint` without walking at all.
+
+use std::cell::Cell;
+
+use crate::count::count;
+use crate::rules::{digits, Policy, Rule};
+
+
+fn from_digits(digits: &[u8], base: u32) -> Option<u64> {
+    digits.iter().try_fold(0u64, |number, &digit| {
+        number.checked_mul(u64::from(base))?.checked_add(u64::from(digit))
+    })
+}
+
+
+/// The smallest number at or after `number` whose digits never decrease, if
+/// it fits in a `u64`.
+pub fn next_non_decreasing(number: u64, base: u32) -> Option<u64> {
+    let mut digits = digits(number, base);
+    if let Some(i) = (1..digits.len()).find(|&i| digits[i] < digits[i - 1]) {
+        let fill = digits[i - 1];
+        digits[i..].iter_mut().for_each(|digit| *digit = fill);
+    }
+    from_digits(&digits, base)
+}
+
+
+/// The largest number at or before `number` whose digits never decrease.
+pub fn previous_non_decreasing(number: u64, base: u32) -> u64 {
+    let mut digits = digits(number, base);
+    if let Some(i) = (1..digits.len()).find(|&i| digits[i] < digits[i - 1]) {
+        // lower the digit before the decrease, moving left while that would
+        // make it smaller than its own predecessor
+        let mut j = i - 1;
+        while j > 0 && digits[j] - 1 < digits[j - 1] {
+            j -= 1;
+        }
+        digits[j] -= 1;
+        digits[j + 1..].iter_mut().for_each(|digit| *digit = (base - 1) as u8);
+    }
+    from_digits(&digits, base).unwrap()
+}
+
+
+/// The passwords in `start..=end` that match `policy`, in order.
+#[derive(Debug, Clone)]
+pub struct Candidates {
+    policy: Policy,
+    skip: bool,
+    /// Whether `count` works for the policy.
+    countable: bool,
+    /// Everything left is in `front..=back`, unless `done`.
+    front: u64,
+    back: u64,
+    done: bool,
+    /// How many matches are left, once counted. Kept up to date as they are
+    /// taken so that `size_hint` only counts once.
+    left: Cell<Option<u64>>,
+}
+
+
+impl Candidates {
+    pub fn new(policy: &Policy, start: u64, end: u64) -> Candidates {
+        Candidates {
+            skip: policy.rules.contains(&Rule::NonDecreasing),
+            countable: count(policy, 0, 0).is_ok(),
+            policy: policy.clone(),
+            front: start,
+            back: end,
+            done: start > end,
+            left: Cell::new(None),
+        }
+    }
+
+    /// The number of matches left, or None if the policy can't be counted.
+    fn remaining(&self) -> Option<u64> {
+        if self.done {
+            return Some(0);
+        }
+        if !self.countable {
+            return None;
+        }
+        if self.left.get().is_none() {
+            self.left.set(count(&self.policy, self.front, self.back).ok());
+        }
+        self.left.get()
+    }
+
+    /// Note that `taken` more matches have been handed out.
+    fn took(&mut self, taken: u64) {
+        if let Some(left) = self.left.get_mut() {
+            *left -= taken;
+        }
+    }
+}
+
+
+impl Iterator for Candidates {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        while !self.done {
+            let number = if self.skip {
+                match next_non_decreasing(self.front, self.policy.base) {
+                    Some(number) if number <= self.back => number,
+                    _ => {
+                        self.done = true;
+                        return None;
+                    }
+                }
+            } else {
+                self.front
+            };
+
+            if number == self.back {
+                self.done = true;
+            } else {
+                self.front = number + 1;
+            }
+            if self.policy.matches(number) {
+                self.took(1);
+                return Some(number);
+            }
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self.remaining() {
+            Some(remaining) => (remaining as usize, Some(remaining as usize)),
+            None => (0, ((self.back - self.front) as usize).checked_add(1)),
+        }
+    }
+
+    fn count(self) -> usize {
+        match self.remaining() {
+            Some(remaining) => remaining as usize,
+            None => self.fold(0, |count, _| count + 1),
+        }
+    }
+
+    /// Counts instead of stepping when it can: binary search for the first
+    /// number with `n + 1` matches at or before it.
+    fn nth(&mut self, n: usize) -> Option<u64> {
+        let remaining = match self.remaining() {
+            Some(remaining) if self.skip && !self.done => remaining,
+            _ => {
+                for _ in 0..n {
+                    self.next()?;
+                }
+                return self.next();
+            }
+        };
+
+        let wanted = n as u64 + 1;
+        if remaining < wanted {
+            self.done = true;
+            self.left.set(Some(0));
+            return None;
+        }
+        let count = |end| count(&self.policy, self.front, end).expect("the policy was counted already");
+        let (mut low, mut high) = (self.front, self.back);
+        while low < high {
+            let middle = low + (high - low) / 2;
+            if count(middle) >= wanted {
+                high = middle;
+            } else {
+                low = middle + 1;
+            }
+        }
+
+        if low == self.back {
+            self.done = true;
+        } else {
+            self.front = low + 1;
+        }
+        self.took(wanted);
+        Some(low)
+    }
+}
+
+
+impl DoubleEndedIterator for Candidates {
+    fn next_back(&mut self) -> Option<u64> {
+        while !self.done {
+            let number = if self.skip { previous_non_decreasing(self.back, self.policy.base) } else { self.back };
+            if number < self.front {
+                self.done = true;
+                return None;
+            }
+
+            if number == self.front {
+                self.done = true;
+            } else {
+                self.back = number - 1;
+            }
+            if self.policy.matches(number) {
+                self.took(1);
+                return Some(number);
+            }
+        }
+        None
+    }
+}
+
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::count::brute_force;
+
+    #[test]
+    fn test_skipping() {
+        assert_eq!(next_non_decreasing(138241, 10), Some(138888));
+        assert_eq!(next_non_decreasing(138888, 10), Some(138888));
+        assert_eq!(next_non_decreasing(674034, 10), Some(677777));
+        assert_eq!(next_non_decreasing(u64::MAX, 10), None);
+        assert_eq!(next_non_decreasing(0b100, 2), Some(0b111));
+
+        assert_eq!(previous_non_decreasing(138241, 10), 137999);
+        assert_eq!(previous_non_decreasing(674034, 10), 669999);
+        assert_eq!(previous_non_decreasing(100, 10), 99);
+        assert_eq!(previous_non_decreasing(332, 10), 299);
+        assert_eq!(previous_non_decreasing(7, 10), 7);
+    }
+
+    #[test]
+    fn test_iterator() {
+        let (start, end) = (138241, 674034);
+        for policy in [Policy::part1(), Policy::part2()] {
+            let expected: Vec<u64> = (start..=end).filter(|&number| policy.matches(number)).collect();
+            let forwards: Vec<u64> = Candidates::new(&policy, start, end).collect();
+            let mut backwards: Vec<u64> = Candidates::new(&policy, start, end).rev().collect();
+            backwards.reverse();
+            assert_eq!(forwards, expected);
+            assert_eq!(backwards, expected);
+
+            assert_eq!(Candidates::new(&policy, start, end).count(), expected.len());
+            assert_eq!(Candidates::new(&policy, start, end).nth(100), Some(expected[100]));
+            assert_eq!(Candidates::new(&policy, start, end).nth(expected.len()), None);
+
+            // nth leaves the iterator just after what it returned, from either end
+            let mut candidates = Candidates::new(&policy, start, end);
+            assert_eq!(candidates.nth(10), Some(expected[10]));
+            assert_eq!(candidates.next(), Some(expected[11]));
+            assert_eq!(candidates.next_back(), expected.last().copied());
+            assert_eq!(candidates.count(), expected.len() - 13);
+        }
+    }
+
+    #[test]
+    fn test_uncountable() {
+        // non-decreasing, but with a rule `count` can't handle
+        let policy = Policy::part1().with(Rule::Straight(3));
+        let (start, end) = (100000, 999999);
+        assert!(count(&policy, start, end).is_err());
+
+        let expected: Vec<u64> = (start..=end).filter(|&number| policy.matches(number)).collect();
+        assert_eq!(Candidates::new(&policy, start, end).next(), Some(111123));
+        for n in [0, 1, 100] {
+            assert_eq!(Candidates::new(&policy, start, end).nth(n), Some(expected[n]));
+        }
+        assert_eq!(Candidates::new(&policy, start, end).nth(expected.len()), None);
+        assert_eq!(Candidates::new(&policy, start, end).count(), expected.len());
+        assert_eq!(Candidates::new(&policy, start, end).size_hint(), (0, Some(900000)));
+    }
+
+    #[test]
+    fn test_size_hint() {
+        let mut candidates = Candidates::new(&Policy::part2(), 138241, 674034);
+        assert_eq!(candidates.size_hint(), (1277, Some(1277)));
+        candidates.next();
+        candidates.next_back();
+        assert_eq!(candidates.size_hint(), (1275, Some(1275)));
+        candidates.nth(5);
+        assert_eq!(candidates.size_hint(), (1269, Some(1269)));
+        assert_eq!(candidates.clone().count(), 1269);
+        assert_eq!(candidates.nth(1269), None);
+        assert_eq!(candidates.size_hint(), (0, Some(0)));
+    }
+
+    #[test]
+    fn test_without_skipping() {
+        // no non-decreasing rule, so every number is looked at
+        let policy = Policy::new().with(Rule::HasExactRun(3));
+        let numbers: Vec<u64> = Candidates::new(&policy, 990, 1200).collect();
+        assert_eq!(numbers, vec![999, 1000, 1110, 1112, 1113, 1114, 1115, 1116, 1117, 1118, 1119]);
+        assert_eq!(Candidates::new(&policy, 0, 5000).count() as u64, brute_force(&policy, 0, 5000));
+        assert_eq!(Candidates::new(&policy, 990, 1200).rev().nth(1), Some(1118));
+    }
+}
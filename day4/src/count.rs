@@ -0,0 +1,254 @@
+//! Counting valid passwords without trying every number.
+//!
+//! Once the digits may never decrease, a password is a multiset of digits
+//! written in order, and there are far fewer of those than numbers: 5005
+//! six-digit ones against 900000 six-digit numbers. `count` walks only those
+//! sequences, digit by digit from the left, and remembers how many ways there
+//! are to finish from each (position, last digit, current run, runs seen so
+//! far) so that each of those is only worked out once. The range bounds are
+//! handled the usual digit DP way, as `count(..=hi) - count(..lo)`.
+//!
+//! Policies that can't be counted that way can still be checked a number at
+//! a time, and `brute_force_parallel` spreads that over every core.
+
+use std::collections::HashMap;
+
+use rayon::prelude::*;
+
+use crate::rules::{digits, Policy, Rule};
+
+
+/// How many numbers `brute_force_parallel` hands to a thread at once.
+pub const CHUNK: u64 = 1 << 16;
+
+
+/// Every number in `start..=end` that matches, the slow way.
+pub fn brute_force(policy: &Policy, start: u64, end: u64) -> u64 {
+    (start..=end).filter(|&number| policy.matches(number)).count() as u64
+}
+
+
+/// Every number in `start..=end` that matches, checked in parallel. The range
+/// is cut into `CHUNK` sized pieces at the same places whatever the number of
+/// threads, and the counts are whole numbers, so the total never depends on
+/// how the pieces were scheduled.
+pub fn brute_force_parallel(policy: &Policy, start: u64, end: u64) -> u64 {
+    if start > end {
+        return 0;
+    }
+    let chunks = (end - start) / CHUNK + 1;
+    (0..chunks).into_par_iter()
+        .map(|chunk| {
+            let low = start + chunk * CHUNK;
+            let high = low.saturating_add(CHUNK - 1).min(end);
+            brute_force(policy, low, high)
+        })
+        .sum()
+}
+
+
+/// The number of matches in `start..=end`. Only works for policies that
+/// include `Rule::NonDecreasing`, which is what keeps the search small.
+pub fn count(policy: &Policy, start: u64, end: u64) -> Result<u64, String> {
+    if !policy.rules.contains(&Rule::NonDecreasing) {
+        return Err(String::from("counting needs a policy with the non-decreasing rule"));
+    }
+
+    let mut start = start;
+    let mut end = end;
+    let mut lengths = None;
+    let mut runs = Vec::new();
+    for rule in &policy.rules {
+        match rule {
+            Rule::Length(length) => match lengths {
+                Some(other) if other != *length => return Ok(0),
+                _ => lengths = Some(*length),
+            },
+            Rule::NonDecreasing => {}
+            Rule::HasRun { min, max } => runs.push((*min, *max)),
+            Rule::HasExactRun(length) => runs.push((*length, *length)),
+            Rule::InRange(range) => {
+                start = start.max(*range.start());
+                end = end.min(*range.end());
+            }
+            Rule::Straight(_) | Rule::Without(_) | Rule::Pairs(_) => {
+                return Err(format!("counting can't handle the rule {:?}", rule));
+            }
+        }
+    }
+    if start > end {
+        return Ok(0);
+    }
+    if runs.len() >= 64 {
+        return Err(String::from("counting handles at most 63 run rules"));
+    }
+
+    let counter = Counter::new(policy.base, runs, lengths);
+    let below = if start == 0 { 0 } else { counter.up_to(start - 1) };
+    Ok(counter.up_to(end) - below)
+}
+
+
+struct Counter {
+    base: u32,
+    /// (min, max) for each run rule.
+    runs: Vec<(usize, usize)>,
+    /// Runs at least this long all look the same to the run rules.
+    cap: usize,
+    length: Option<usize>,
+}
+
+
+/// The part of the search that changes as it goes.
+struct Search<'a> {
+    counter: &'a Counter,
+    /// Digits of the upper bound, when there is one.
+    bound: Vec<u8>,
+    memo: HashMap<(usize, u32, usize, u64), u64>,
+}
+
+
+impl Counter {
+    fn new(base: u32, runs: Vec<(usize, usize)>, length: Option<usize>) -> Counter {
+        let cap = runs.iter()
+            .map(|&(min, max)| if max == usize::MAX { min } else { max + 1 })
+            .chain(runs.iter().map(|&(min, _)| min))
+            .max()
+            .unwrap_or(1)
+            .max(1);
+        Counter { base, runs, cap, length }
+    }
+
+    /// Mark the run rules a finished run of `run` digits satisfies.
+    fn close(&self, run: usize, mut mask: u64) -> u64 {
+        for (i, &(min, max)) in self.runs.iter().enumerate() {
+            if min <= run && run <= max {
+                mask |= 1 << i;
+            }
+        }
+        mask
+    }
+
+    /// Matches in `0..=limit`.
+    fn up_to(&self, limit: u64) -> u64 {
+        let limit = digits(limit, self.base);
+        let mut total = 0;
+        for length in 1..=limit.len() {
+            if self.length.is_some_and(|wanted| wanted != length) {
+                continue;
+            }
+            let bound = if length == limit.len() { limit.clone() } else { Vec::new() };
+            let mut search = Search { counter: self, bound, memo: HashMap::new() };
+            total += search.go(length, 0, 0, 0, 0, length == limit.len());
+        }
+        total
+    }
+}
+
+
+impl Search<'_> {
+    /// Ways to finish a `length` digit number from position `pos`, with the
+    /// current run of `run` digits equal to `last`.
+    fn go(&mut self, length: usize, pos: usize, last: u32, run: usize, mask: u64, tight: bool) -> u64 {
+        let counter = self.counter;
+        if pos == length {
+            let all = (1u64 << counter.runs.len()) - 1;
+            return (counter.close(run, mask) == all) as u64;
+        }
+        if !tight {
+            if let Some(&ways) = self.memo.get(&(pos, last, run, mask)) {
+                return ways;
+            }
+        }
+
+        // no leading zeroes, except for 0 itself; after that never below the last digit
+        let lowest = if pos > 0 { last } else if length == 1 { 0 } else { 1 };
+        let highest = if tight { u32::from(self.bound[pos]) } else { counter.base - 1 };
+
+        let mut ways = 0;
+        for digit in lowest..=highest {
+            let (run, mask) = if pos > 0 && digit == last {
+                ((run + 1).min(counter.cap), mask)
+            } else if pos > 0 {
+                (1, counter.close(run, mask))
+            } else {
+                (1, mask)
+            };
+            ways += self.go(length, pos + 1, digit, run, mask, tight && digit == highest);
+        }
+
+        if !tight {
+            self.memo.insert((pos, last, run, mask), ways);
+        }
+        ways
+    }
+}
+
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_puzzle() {
+        let (start, end) = (138241, 674034);
+        assert_eq!(count(&Policy::part1(), start, end), Ok(brute_force(&Policy::part1(), start, end)));
+        assert_eq!(count(&Policy::part2(), start, end), Ok(brute_force(&Policy::part2(), start, end)));
+        assert_eq!(count(&Policy::part2(), start, end), Ok(1277));
+    }
+
+    #[test]
+    fn test_agrees_with_brute_force() {
+        let policies = vec![
+            Policy::new().with(Rule::NonDecreasing),
+            Policy::new().with(Rule::NonDecreasing).with(Rule::HasRun { min: 2, max: usize::MAX }),
+            Policy::new().with(Rule::NonDecreasing).with(Rule::HasExactRun(2)),
+            Policy::new().with(Rule::NonDecreasing).with(Rule::HasRun { min: 2, max: 3 }).with(Rule::HasExactRun(1)),
+            Policy::new().with(Rule::NonDecreasing).with(Rule::Length(4)).with(Rule::InRange(1500..=8000)),
+        ];
+        let ranges = [(0, 0), (0, 9), (10, 11), (0, 99_999), (1234, 56789), (99_999, 100_000)];
+
+        for base in [10, 2, 3, 7, 16] {
+            for policy in &policies {
+                let policy = policy.clone().in_base(base);
+                for &(start, end) in &ranges {
+                    let expected = brute_force(&policy, start, end);
+                    assert_eq!(count(&policy, start, end), Ok(expected), "{:?} over {}..={}", policy, start, end);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_long_numbers() {
+        // non-decreasing 18 digit numbers use only 1-9, so there are C(18 + 8, 8) of them
+        let policy = Policy::new().with(Rule::NonDecreasing).with(Rule::Length(18));
+        assert_eq!(count(&policy, 0, u64::MAX), Ok(1_562_275));
+
+        // part 2 already asks for six digits, so asking for twelve as well matches nothing
+        assert_eq!(count(&Policy::part2().with(Rule::Length(12)), 0, u64::MAX), Ok(0));
+
+        let policy = Policy::new().with(Rule::Length(12)).with(Rule::NonDecreasing).with(Rule::HasExactRun(2));
+        let total = count(&policy, 100_000_000_000, 999_999_999_999).unwrap();
+        let halves = count(&policy, 100_000_000_000, 444_444_444_444).unwrap() + count(&policy, 444_444_444_445, 999_999_999_999).unwrap();
+        assert_eq!(total, halves);
+        assert!(total > 0);
+
+        assert!(count(&Policy::new().with(Rule::HasExactRun(2)), 0, 10).is_err());
+    }
+
+    #[test]
+    fn test_brute_force_parallel() {
+        let (start, end) = (138241, 674034);
+        assert_eq!(brute_force_parallel(&Policy::part1(), start, end), 1890);
+        assert_eq!(brute_force_parallel(&Policy::part2(), start, end), 1277);
+
+        // ranges that start or end right at a chunk boundary, or are smaller than one
+        let policy = Policy::new().with(Rule::HasExactRun(3));
+        for &(start, end) in &[(0, CHUNK - 1), (CHUNK - 1, 3 * CHUNK), (5, 5), (7, 6), (1000, 1200)] {
+            assert_eq!(brute_force_parallel(&policy, start, end), brute_force(&policy, start, end), "{}..={}", start, end);
+        }
+        assert_eq!(brute_force_parallel(&policy, u64::MAX - 2 * CHUNK, u64::MAX), brute_force(&policy, u64::MAX - 2 * CHUNK, u64::MAX));
+    }
+}
@@ -0,0 +1,195 @@
+//! Saying why a password was turned down.
+//!
+//! `Policy::matches` only answers yes or no. `explain` checks every rule
+//! anyway and keeps what went wrong with each, down to the digits involved:
+//! 223450 fails the non-decreasing rule with a decrease at digits 5-6, 5→0.
+
+use std::fmt;
+
+use crate::rules::{digits, distinct_pairs, first_decrease, first_excluded, repeats, Policy, Rule};
+
+
+/// What a failing rule found wrong with the number.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Failure {
+    /// The number has `found` digits.
+    Length { found: usize, wanted: usize },
+    /// The digit at `position` (counting from 0) is bigger than the next one.
+    Decrease { position: usize, from: u8, to: u8 },
+    /// None of the runs are the right length. Holds every run of two or more
+    /// as (digit, length), which is empty when no adjacent digits are equal.
+    Runs { runs: Vec<(u8, usize)>, min: usize, max: usize },
+    OutOfRange { start: u64, end: u64 },
+    NoStraight { length: usize },
+    /// The digit at `position` is one of the excluded ones.
+    Excluded { position: usize, digit: u8 },
+    /// Only `found` different digits appear as pairs.
+    Pairs { found: usize, wanted: usize },
+}
+
+
+/// One rule of the policy, and what was wrong if it failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Verdict {
+    pub rule: Rule,
+    pub failure: Option<Failure>,
+}
+
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+    pub number: u64,
+    pub verdicts: Vec<Verdict>,
+}
+
+
+impl Report {
+    pub fn is_valid(&self) -> bool {
+        self.verdicts.iter().all(|verdict| verdict.failure.is_none())
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = &Verdict> {
+        self.verdicts.iter().filter(|verdict| verdict.failure.is_some())
+    }
+}
+
+
+/// What `rule` found wrong, if `Rule::check` says it failed.
+fn diagnose(rule: &Rule, number: u64, digits: &[u8]) -> Option<Failure> {
+    if rule.check(number, digits) {
+        return None;
+    }
+    let failure = match rule {
+        Rule::Length(wanted) => Failure::Length { found: digits.len(), wanted: *wanted },
+        Rule::NonDecreasing => {
+            let position = first_decrease(digits).expect("a failed non-decreasing rule has a decrease");
+            Failure::Decrease { position, from: digits[position], to: digits[position + 1] }
+        }
+        Rule::HasRun { min, max } => Failure::Runs { runs: repeats(digits), min: *min, max: *max },
+        Rule::HasExactRun(length) => Failure::Runs { runs: repeats(digits), min: *length, max: *length },
+        Rule::InRange(range) => Failure::OutOfRange { start: *range.start(), end: *range.end() },
+        Rule::Straight(length) => Failure::NoStraight { length: *length },
+        Rule::Without(excluded) => {
+            let position = first_excluded(digits, excluded).expect("a failed without rule has an excluded digit");
+            Failure::Excluded { position, digit: digits[position] }
+        }
+        Rule::Pairs(wanted) => Failure::Pairs { found: distinct_pairs(digits), wanted: *wanted },
+    };
+    Some(failure)
+}
+
+
+/// Check `number` against every rule of `policy`, not stopping at the first
+/// that fails.
+pub fn explain(policy: &Policy, number: u64) -> Report {
+    let digits = digits(number, policy.base);
+    let verdicts = policy.rules.iter()
+        .map(|rule| Verdict { rule: rule.clone(), failure: diagnose(rule, number, &digits) })
+        .collect();
+    Report { number, verdicts }
+}
+
+
+/// How a run length rule reads, e.g. "an exact pair" or "at least 3 long".
+fn wanted_run(min: usize, max: usize) -> String {
+    match (min, max) {
+        (2, 2) => String::from("an exact pair"),
+        (min, max) if min == max => format!("exactly {} long", min),
+        (min, usize::MAX) => format!("at least {} long", min),
+        (min, max) => format!("between {} and {} long", min, max),
+    }
+}
+
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Failure::Length { found, wanted } => write!(f, "has {} digits, not {}", found, wanted),
+            Failure::Decrease { position, from, to } => {
+                write!(f, "decrease at digits {}-{}: {}→{}", position + 1, position + 2, from, to)
+            }
+            Failure::Runs { runs, .. } if runs.is_empty() => write!(f, "no two adjacent digits are equal"),
+            Failure::Runs { runs, min, max } => {
+                let runs: Vec<String> = runs.iter().map(|(digit, length)| format!("{}×{}", length, digit)).collect();
+                let (noun, verb) = if runs.len() == 1 { ("run", "is") } else { ("runs", "are") };
+                write!(f, "{} of {} {} not {}", noun, runs.join(", "), verb, wanted_run(*min, *max))
+            }
+            Failure::OutOfRange { start, end } => write!(f, "outside {}-{}", start, end),
+            Failure::NoStraight { length } => write!(f, "no {} digits in a row go up one at a time", length),
+            Failure::Excluded { position, digit } => write!(f, "digit {} is {}, which is excluded", position + 1, digit),
+            Failure::Pairs { found, wanted } => write!(f, "pairs of {} different digits, not {}", found, wanted),
+        }
+    }
+}
+
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let outcome = if self.is_valid() { "valid" } else { "rejected" };
+        writeln!(f, "{}: {}", self.number, outcome)?;
+        for verdict in &self.verdicts {
+            match &verdict.failure {
+                None => writeln!(f, "  pass  {}", verdict.rule)?,
+                Some(failure) => writeln!(f, "  FAIL  {}: {}", verdict.rule, failure)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn reasons(policy: &Policy, number: u64) -> Vec<String> {
+        explain(policy, number).failures().map(|verdict| verdict.failure.as_ref().unwrap().to_string()).collect()
+    }
+
+    #[test]
+    fn test_reasons() {
+        assert!(reasons(&Policy::part2(), 112233).is_empty());
+        assert_eq!(reasons(&Policy::part1(), 223450), vec!["decrease at digits 5-6: 5→0"]);
+        assert_eq!(reasons(&Policy::part1(), 123789), vec!["no two adjacent digits are equal"]);
+        assert_eq!(reasons(&Policy::part2(), 123444), vec!["run of 3×4 is not an exact pair"]);
+        assert_eq!(reasons(&Policy::part2(), 111444), vec!["runs of 3×1, 3×4 are not an exact pair"]);
+        assert_eq!(reasons(&Policy::part2(), 1230), vec!["has 4 digits, not 6", "decrease at digits 3-4: 3→0", "no two adjacent digits are equal"]);
+
+        let ranged = Policy::new().with(Rule::InRange(138241..=674034)).with(Rule::HasRun { min: 3, max: 4 });
+        assert_eq!(reasons(&ranged, 11), vec!["outside 138241-674034", "run of 2×1 is not between 3 and 4 long"]);
+
+        let text = Policy::new().with(Rule::Straight(3)).with(Rule::Without(vec![0])).with(Rule::Pairs(2));
+        assert!(reasons(&text, 1123455).is_empty());
+        assert_eq!(reasons(&text, 112405), vec!["no 3 digits in a row go up one at a time", "digit 5 is 0, which is excluded", "pairs of 1 different digits, not 2"]);
+    }
+
+    #[test]
+    fn test_agrees_with_matches() {
+        let policies = [
+            Policy::part1(),
+            Policy::part2(),
+            Policy::part2().with(Rule::InRange(200000..=300000)).in_base(7),
+            Policy::new().with(Rule::Straight(3)).with(Rule::Without(vec![0, 7])).with(Rule::Pairs(2)),
+            Policy::new().with(Rule::NonDecreasing).with(Rule::Straight(2)).with(Rule::Pairs(1)).in_base(5),
+        ];
+        for policy in &policies {
+            for number in (0..1_000_000).step_by(37) {
+                assert_eq!(explain(policy, number).is_valid(), policy.matches(number), "{} with {:?}", number, policy);
+            }
+        }
+    }
+
+    #[test]
+    fn test_report() {
+        let report = explain(&Policy::part2(), 123444);
+        assert_eq!(report.to_string(), "\
+123444: rejected
+  pass  6 digits
+  pass  digits never decrease
+  FAIL  a run of exactly 2 equal digits: run of 3×4 is not an exact pair
+");
+        assert_eq!(report.failures().count(), 1);
+        assert!(explain(&Policy::part1(), 123444).is_valid());
+    }
+}
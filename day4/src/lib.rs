@@ -0,0 +1,49 @@
+//! Password search for day 4, shared between the binary and the benchmarks.
+
+pub mod candidates;
+pub mod count;
+pub mod explain;
+pub mod range;
+pub mod rules;
+pub mod text;
+
+use rules::Policy;
+
+
+/// Whether a given number is a possible password, by the part 2 rules.
+pub fn matches(input: i32) -> bool {
+    input >= 0 && Policy::part2().matches(input as u64)
+}
+
+
+
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+mod test {
+    use super::matches;
+
+    #[test]
+    fn test_matching(){
+
+        assert_eq!(matches(111111), false);
+        assert_eq!(matches(223456), true);
+        assert_eq!(matches(223345), true);
+
+        assert_eq!(matches(223450), false);
+        assert_eq!(matches(123789), false);
+
+        // too short
+        assert_eq!(matches(111), false);
+        assert_eq!(matches(12233), false);
+
+        // too long
+        assert_eq!(matches(11223344), false);
+        assert_eq!(matches(1123450), false);
+
+        assert_eq!(matches(0), false);
+
+        assert_eq!(matches(112233), true); // meets these criteria because the digits never decrease and all repeated digits are exactly two digits long.
+        assert_eq!(matches(123444), false); // no longer meets the criteria (the repeated 44 is part of a larger group of 444).
+        assert_eq!(matches(111122), true); // meets the criteria (even though 1 is repeated more than twice, it still contains a double 22)
+    }
+}
\ No newline at end of file
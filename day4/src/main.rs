@@ -30,89 +30,89 @@ How many different passwords within the range given in your puzzle input meet al
 
 
 */
-use std::cmp::min;
+use std::env;
+use std::fs::read_to_string;
+use std::path::Path;
 
+use day4::candidates::Candidates;
+use day4::count::count;
+use day4::explain::explain;
+use day4::range::{parse_range, Bounds};
+use day4::rules::Policy;
 
-fn matches(input: i32) -> bool {
-    // return true or false if a given number is a possible password
-    // println!("checking:{}", input);
-    // println!("{}", input);
 
-    let mut counts = [0; 10];
-
-    let mut input = input;
-    let mut last = input % 10;
-    counts[last as usize] += 1;
-    input = input / 10;
-
-    while input != 0 {
-        let digit = input % 10;
-        input = input / 10;
-
-        counts[digit as usize] += 1;
-
-        if digit > last {
-            return false
-        }
-
-        last = digit;
+fn main() {
+    /*
+    count the possible passwords in a range, by the part 1 and the part 2 rules
+    the range is given either directly (`138241-674034`) or as the path to a file containing it
+    the end of the range is included, as in the puzzle, unless --exclusive is given
+    --list also prints every part 1 match, marking those that match part 2 as well
+    --explain N checks a single number instead, saying which rules of each part it breaks
+    */
+    let args: Vec<String> = env::args().skip(1).collect();
+    let exclusive = args.iter().any(|arg| arg == "--exclusive");
+    let list = args.iter().any(|arg| arg == "--list");
+    let explaining = args.iter().any(|arg| arg == "--explain");
+    let unknown: Vec<&String> = args.iter()
+        .filter(|arg| arg.starts_with("--") && !["--exclusive", "--inclusive", "--list", "--explain"].contains(&arg.as_str()))
+        .collect();
+    let inputs: Vec<&String> = args.iter().filter(|arg| !arg.starts_with("--")).collect();
+    let inclusive = args.iter().any(|arg| arg == "--inclusive");
+    if inputs.len() != 1 || !unknown.is_empty() || (inclusive && exclusive) || (explaining && (list || inclusive || exclusive)) {
+        println!("Please input a single range (like 138241-674034) or the path to a file containing one");
+        println!("optionally with --inclusive (the default) or --exclusive, and --list");
+        println!("or --explain followed by a single number");
+        return
     }
 
-    if (counts.iter().sum::<i32>() == 6) & counts.contains(&2) {
-        return true;
-    } else {
-        return false
+    if explaining {
+        let number = match inputs[0].parse::<u64>() {
+            Ok(number) => number,
+            Err(_) => {
+                println!("{:?} is not a number", inputs[0]);
+                return
+            }
+        };
+        print!("part one {}", explain(&Policy::part1(), number));
+        print!("part two {}", explain(&Policy::part2(), number));
+        return
     }
-}
-
 
-fn count_matching(start: i32, end: i32) -> u32 {
-    // return the number of numbers that meet the above criteria
-    let mut count = 0;
-
-    for i in start..end {
-        if matches(i) {
-            count += 1;
+    let text = if Path::new(inputs[0]).is_file() {
+        match read_to_string(inputs[0]) {
+            Ok(text) => text,
+            Err(err) => {
+                println!("failed to load input: {}", err);
+                return
+            }
+        }
+    } else {
+        inputs[0].clone()
+    };
+
+    let bounds = if exclusive { Bounds::Exclusive } else { Bounds::Inclusive };
+    let range = match parse_range(&text, bounds) {
+        Ok(range) => range,
+        Err(err) => {
+            println!("bad range: {}", err);
+            return
+        }
+    };
+    let (start, end) = (*range.start(), *range.end());
+
+    let part1 = Policy::part1();
+    let part2 = Policy::part2();
+    if list {
+        for number in Candidates::new(&part1, start, end) {
+            if part2.matches(number) {
+                println!("{} (part 2)", number);
+            } else {
+                println!("{}", number);
+            }
         }
     }
 
-    count
-}
-
-
-
-fn main() {
-    println!("Number of possible passwords: {}", count_matching(138241, 674034));
+    // both policies never decrease, so they can always be counted
+    println!("part one answer: {}", count(&part1, start, end).unwrap());
+    println!("part two answer: {}", count(&part2, start, end).unwrap());
 }
-
-
-
-#[cfg(test)]
-mod test {
-    use super::matches;
-
-    #[test]
-    fn test_matching(){
-
-        assert_eq!(matches(111111), false);
-        assert_eq!(matches(223456), true);
-        assert_eq!(matches(223345), true);
-
-        assert_eq!(matches(223450), false);
-        assert_eq!(matches(123789), false);
-
-        // too short
-        assert_eq!(matches(111), false);
-        assert_eq!(matches(12233), false);
-
-        // too long
-        assert_eq!(matches(11223344), false);
-        assert_eq!(matches(1123450), false);
-
-        assert_eq!(matches(0), false);
-
-        assert_eq!(matches(112233), true); // meets these criteria because the digits never decrease and all repeated digits are exactly two digits long.
-        assert_eq!(matches(123444), false); // no longer meets the criteria (the repeated 44 is part of a larger group of 444).
-        assert_eq!(matches(111122), true); // meets the criteria (even though 1 is repeated more than twice, it still contains a double 22)
-    }
-}
\ No newline at end of file
@@ -0,0 +1,51 @@
+//! The puzzle input: a range written as `138241-674034`.
+
+use std::ops::RangeInclusive;
+
+
+/// Whether the number after the dash is part of the range. The puzzle means
+/// inclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bounds {
+    Inclusive,
+    Exclusive,
+}
+
+
+pub fn parse_range(text: &str, bounds: Bounds) -> Result<RangeInclusive<u64>, String> {
+    let text = text.trim();
+    let (start, end) = text.split_once('-')
+        .ok_or_else(|| format!("expected a range like 138241-674034, not {:?}", text))?;
+    let number = |part: &str| part.trim().parse::<u64>().map_err(|_| format!("{:?} is not a number", part.trim()));
+    let (start, end) = (number(start)?, number(end)?);
+
+    let end = match bounds {
+        Bounds::Inclusive => end,
+        Bounds::Exclusive if end > start => end - 1,
+        Bounds::Exclusive => return Err(format!("{}-{} is empty when the end is excluded", start, end)),
+    };
+    if start > end {
+        return Err(format!("the range {}-{} runs backwards", start, end));
+    }
+    Ok(start..=end)
+}
+
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(parse_range("138241-674034", Bounds::Inclusive), Ok(138241..=674034));
+        assert_eq!(parse_range(" 138241 - 674034\n", Bounds::Exclusive), Ok(138241..=674033));
+        assert_eq!(parse_range("5-5", Bounds::Inclusive), Ok(5..=5));
+
+        assert_eq!(parse_range("5-5", Bounds::Exclusive), Err(String::from("5-5 is empty when the end is excluded")));
+        assert_eq!(parse_range("9-5", Bounds::Inclusive), Err(String::from("the range 9-5 runs backwards")));
+        assert_eq!(parse_range("138241", Bounds::Inclusive), Err(String::from("expected a range like 138241-674034, not \"138241\"")));
+        assert_eq!(parse_range("-5-10", Bounds::Inclusive), Err(String::from("\"\" is not a number")));
+        assert_eq!(parse_range("1-x", Bounds::Inclusive), Err(String::from("\"x\" is not a number")));
+    }
+}
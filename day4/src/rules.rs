@@ -0,0 +1,287 @@
+//! The password rules from the puzzle, split up so they can be combined.
+//!
+//! Part 1 is six digits, never decreasing, with at least a pair of equal
+//! adjacent digits. Part 2 tightens the pair to a run of exactly two.
+//!
+//! A "digit" is really a position in an ordered alphabet, so the same rules
+//! work for text too (see `text`). `Straight`, `Without` and `Pairs` are
+//! mostly for that: they make up the "increasing straight, no i, o or l, two
+//! pairs" kind of policy.
+
+use std::fmt;
+use std::ops::RangeInclusive;
+
+
+/// The digits of a number in the given base, most significant first.
+pub fn digits(number: u64, base: u32) -> Vec<u8> {
+    let mut result = Vec::new();
+    let mut number = number;
+    loop {
+        result.push((number % base as u64) as u8);
+        number /= base as u64;
+        if number == 0 {
+            break;
+        }
+    }
+    result.reverse();
+    result
+}
+
+
+/// Lengths of the runs of equal adjacent digits, in order.
+pub fn runs(digits: &[u8]) -> Vec<usize> {
+    let mut result: Vec<usize> = Vec::new();
+    for (i, digit) in digits.iter().enumerate() {
+        if i > 0 && digits[i - 1] == *digit {
+            *result.last_mut().unwrap() += 1;
+        } else {
+            result.push(1);
+        }
+    }
+    result
+}
+
+
+/// The runs of two or more equal digits, as (digit, length).
+pub fn repeats(digits: &[u8]) -> Vec<(u8, usize)> {
+    let mut start = 0;
+    let mut result = Vec::new();
+    for length in runs(digits) {
+        if length >= 2 {
+            result.push((digits[start], length));
+        }
+        start += length;
+    }
+    result
+}
+
+
+/// Where the first digit bigger than the one after it is.
+pub fn first_decrease(digits: &[u8]) -> Option<usize> {
+    digits.windows(2).position(|pair| pair[0] > pair[1])
+}
+
+
+/// Where the first of the `excluded` digits is.
+pub fn first_excluded(digits: &[u8], excluded: &[u8]) -> Option<usize> {
+    digits.iter().position(|digit| excluded.contains(digit))
+}
+
+
+/// Where the first run of `length` digits going up one at a time starts.
+pub fn straight(digits: &[u8], length: usize) -> Option<usize> {
+    if length == 0 {
+        return Some(0);
+    }
+    digits.windows(length)
+        .position(|window| window.windows(2).all(|pair| u16::from(pair[1]) == u16::from(pair[0]) + 1))
+}
+
+
+/// How many different digits appear as a pair (or longer run).
+pub fn distinct_pairs(digits: &[u8]) -> usize {
+    let mut paired: Vec<u8> = repeats(digits).iter().map(|&(digit, _)| digit).collect();
+    paired.sort_unstable();
+    paired.dedup();
+    paired.len()
+}
+
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Rule {
+    /// Exactly this many digits.
+    Length(usize),
+    /// Going from left to right, the digits never decrease.
+    NonDecreasing,
+    /// Some run of equal digits is between `min` and `max` long, inclusive.
+    HasRun { min: usize, max: usize },
+    /// Some run of equal digits is exactly this long.
+    HasExactRun(usize),
+    InRange(RangeInclusive<u64>),
+    /// Somewhere this many digits go up one at a time, like 345 or `abc`.
+    Straight(usize),
+    /// None of these digits appear.
+    Without(Vec<u8>),
+    /// At least this many different digits appear as a pair.
+    Pairs(usize),
+}
+
+
+impl Rule {
+    pub fn check(&self, number: u64, digits: &[u8]) -> bool {
+        match self {
+            Rule::Length(length) => digits.len() == *length,
+            Rule::NonDecreasing => first_decrease(digits).is_none(),
+            Rule::HasRun { min, max } => runs(digits).iter().any(|run| min <= run && run <= max),
+            Rule::HasExactRun(length) => runs(digits).contains(length),
+            Rule::InRange(range) => range.contains(&number),
+            Rule::Straight(length) => straight(digits, *length).is_some(),
+            Rule::Without(excluded) => first_excluded(digits, excluded).is_none(),
+            Rule::Pairs(count) => distinct_pairs(digits) >= *count,
+        }
+    }
+}
+
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Rule::Length(length) => write!(f, "{} digits", length),
+            Rule::NonDecreasing => write!(f, "digits never decrease"),
+            Rule::HasRun { min, max: usize::MAX } => write!(f, "a run of at least {} equal digits", min),
+            Rule::HasRun { min, max } => write!(f, "a run of {} to {} equal digits", min, max),
+            Rule::HasExactRun(length) => write!(f, "a run of exactly {} equal digits", length),
+            Rule::InRange(range) => write!(f, "within {}-{}", range.start(), range.end()),
+            Rule::Straight(length) => write!(f, "a straight of {} digits", length),
+            Rule::Without(excluded) => {
+                let excluded: Vec<String> = excluded.iter().map(u8::to_string).collect();
+                write!(f, "none of the digits {}", excluded.join(", "))
+            }
+            Rule::Pairs(count) => write!(f, "pairs of {} different digits", count),
+        }
+    }
+}
+
+
+/// A password is valid if it passes every rule.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Policy {
+    pub rules: Vec<Rule>,
+    /// The base the digit rules look at the number in, 10 unless changed.
+    pub base: u32,
+}
+
+
+impl Default for Policy {
+    fn default() -> Policy {
+        Policy::new()
+    }
+}
+
+
+impl Policy {
+    pub fn new() -> Policy {
+        Policy { rules: Vec::new(), base: 10 }
+    }
+
+    /// Look at the digits in another base, from 2 up to 256.
+    ///
+    /// # Panics
+    ///
+    /// On any other base, since digits are kept as bytes.
+    pub fn in_base(mut self, base: u32) -> Policy {
+        assert!((2..=256).contains(&base), "unsupported base {}", base);
+        self.base = base;
+        self
+    }
+
+    pub fn with(mut self, rule: Rule) -> Policy {
+        self.rules.push(rule);
+        self
+    }
+
+    /// Six digits that never decrease, with at least one pair.
+    pub fn part1() -> Policy {
+        Policy::new()
+            .with(Rule::Length(6))
+            .with(Rule::NonDecreasing)
+            .with(Rule::HasRun { min: 2, max: usize::MAX })
+    }
+
+    /// As part 1, but the pair may not be part of a longer run.
+    pub fn part2() -> Policy {
+        Policy::new()
+            .with(Rule::Length(6))
+            .with(Rule::NonDecreasing)
+            .with(Rule::HasExactRun(2))
+    }
+
+    pub fn matches(&self, number: u64) -> bool {
+        let digits = digits(number, self.base);
+        self.rules.iter().all(|rule| rule.check(number, &digits))
+    }
+}
+
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_digits() {
+        assert_eq!(digits(0, 10), vec![0]);
+        assert_eq!(digits(138241, 10), vec![1, 3, 8, 2, 4, 1]);
+        assert_eq!(digits(0b110, 2), vec![1, 1, 0]);
+        assert_eq!(digits(255, 16), vec![15, 15]);
+        assert_eq!(runs(&digits(111122, 10)), vec![4, 2]);
+        assert_eq!(runs(&digits(123444, 10)), vec![1, 1, 1, 3]);
+        assert_eq!(repeats(&digits(1114455, 10)), vec![(1, 3), (4, 2), (5, 2)]);
+        assert_eq!(straight(&[0, 2, 3, 4], 3), Some(1));
+        assert_eq!(straight(&[254, 255, 0], 3), None);
+        assert_eq!(distinct_pairs(&digits(1122113, 10)), 2);
+    }
+
+    #[test]
+    fn test_rules() {
+        let check = |rule: Rule, number| rule.check(number, &digits(number, 10));
+
+        assert!(check(Rule::Length(6), 111111));
+        assert!(!check(Rule::Length(6), 12233));
+        assert!(!check(Rule::Length(6), 1123450));
+
+        assert!(check(Rule::NonDecreasing, 111123));
+        assert!(check(Rule::NonDecreasing, 135679));
+        assert!(!check(Rule::NonDecreasing, 223450));
+
+        assert!(check(Rule::HasRun { min: 2, max: usize::MAX }, 123444));
+        assert!(!check(Rule::HasRun { min: 2, max: usize::MAX }, 123789));
+        assert!(check(Rule::HasRun { min: 3, max: 4 }, 111122));
+        assert!(!check(Rule::HasRun { min: 3, max: 3 }, 111122));
+
+        assert!(check(Rule::HasExactRun(2), 111122));
+        assert!(!check(Rule::HasExactRun(2), 123444));
+
+        assert!(check(Rule::InRange(138241..=674034), 674034));
+        assert!(!check(Rule::InRange(138241..=674034), 138240));
+
+        assert!(check(Rule::Straight(3), 124568));
+        assert!(!check(Rule::Straight(3), 124578));
+        assert!(check(Rule::Straight(1), 7));
+
+        assert!(check(Rule::Without(vec![0, 9]), 12345));
+        assert!(!check(Rule::Without(vec![0, 9]), 12390));
+
+        assert!(check(Rule::Pairs(2), 112333));
+        assert!(!check(Rule::Pairs(2), 111111));
+        assert!(!check(Rule::Pairs(2), 121233));
+    }
+
+    #[test]
+    fn test_policies() {
+        // the examples from part 1
+        assert!(Policy::part1().matches(111111));
+        assert!(!Policy::part1().matches(223450));
+        assert!(!Policy::part1().matches(123789));
+
+        // and from part 2
+        assert!(Policy::part2().matches(112233));
+        assert!(!Policy::part2().matches(123444));
+        assert!(Policy::part2().matches(111122));
+        assert!(!Policy::part2().matches(111111));
+
+        let ranged = Policy::part1().with(Rule::InRange(200000..=300000));
+        assert!(ranged.matches(222222));
+        assert!(!ranged.matches(333333));
+
+        // 0x11223 in hex, 0b1011 in binary
+        assert!(Policy::new().with(Rule::NonDecreasing).with(Rule::HasExactRun(2)).in_base(16).matches(0x11223));
+        assert!(!Policy::new().with(Rule::NonDecreasing).in_base(2).matches(0b1011));
+    }
+
+    #[test]
+    #[should_panic(expected = "unsupported base 257")]
+    fn test_base_too_big() {
+        Policy::new().in_base(257);
+    }
+}
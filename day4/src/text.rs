@@ -0,0 +1,233 @@
+//! Passwords that are text rather than numbers.
+//!
+//! The rules only ever look at digits, so a string can be checked by writing
+//! each character as its position in an ordered alphabet: in `a..=z`, "abc"
+//! is the digits 0, 1, 2. Non-decreasing then means the letters are in
+//! alphabetical order, and a straight is something like "xyz".
+
+use std::fmt;
+
+use crate::rules::{Policy, Rule};
+
+
+/// The characters a password is made of, in order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Alphabet {
+    symbols: Vec<char>,
+}
+
+
+impl Alphabet {
+    /// Between 2 and 256 different characters, smallest first.
+    pub fn new(symbols: &str) -> Result<Alphabet, String> {
+        let symbols: Vec<char> = symbols.chars().collect();
+        if !(2..=256).contains(&symbols.len()) {
+            return Err(format!("an alphabet needs 2 to 256 characters, not {}", symbols.len()));
+        }
+        if let Some(i) = (1..symbols.len()).find(|&i| symbols[..i].contains(&symbols[i])) {
+            return Err(format!("{:?} is in the alphabet twice", symbols[i]));
+        }
+        Ok(Alphabet { symbols })
+    }
+
+    pub fn decimal() -> Alphabet {
+        Alphabet::new("0123456789").unwrap()
+    }
+
+    pub fn hex() -> Alphabet {
+        Alphabet::new("0123456789abcdef").unwrap()
+    }
+
+    pub fn lowercase() -> Alphabet {
+        Alphabet::new("abcdefghijklmnopqrstuvwxyz").unwrap()
+    }
+
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    pub fn index(&self, symbol: char) -> Option<u8> {
+        self.symbols.iter().position(|&other| other == symbol).map(|i| i as u8)
+    }
+
+    pub fn encode(&self, text: &str) -> Result<Vec<u8>, String> {
+        text.chars()
+            .map(|symbol| self.index(symbol).ok_or_else(|| format!("{:?} is not in the alphabet", symbol)))
+            .collect()
+    }
+
+    pub fn decode(&self, digits: &[u8]) -> String {
+        digits.iter().map(|&digit| self.symbols[digit as usize]).collect()
+    }
+}
+
+
+impl fmt::Display for Alphabet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.symbols.iter().collect::<String>())
+    }
+}
+
+
+/// A policy for passwords written in `alphabet`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextPolicy {
+    pub alphabet: Alphabet,
+    pub policy: Policy,
+}
+
+
+impl TextPolicy {
+    pub fn new(alphabet: Alphabet) -> TextPolicy {
+        let policy = Policy::new().in_base(alphabet.len() as u32);
+        TextPolicy { alphabet, policy }
+    }
+
+    pub fn with(mut self, rule: Rule) -> TextPolicy {
+        self.policy = self.policy.with(rule);
+        self
+    }
+
+    /// Forbid the given characters. Ones outside the alphabet can't appear
+    /// anyway, so they are left out.
+    pub fn without(self, symbols: &str) -> TextPolicy {
+        let excluded = symbols.chars().filter_map(|symbol| self.alphabet.index(symbol)).collect();
+        self.with(Rule::Without(excluded))
+    }
+
+    /// Eight lowercase letters with an increasing straight of three, no i, o
+    /// or l, and pairs of two different letters.
+    pub fn corporate() -> TextPolicy {
+        TextPolicy::new(Alphabet::lowercase())
+            .with(Rule::Length(8))
+            .with(Rule::Straight(3))
+            .without("iol")
+            .with(Rule::Pairs(2))
+    }
+
+    fn check(&self, digits: &[u8]) -> bool {
+        // the value only matters to `Rule::InRange`, and saturates for long text
+        let base = u64::from(self.policy.base);
+        let number = digits.iter().fold(0u64, |number, &digit| number.saturating_mul(base).saturating_add(u64::from(digit)));
+        self.policy.rules.iter().all(|rule| rule.check(number, digits))
+    }
+
+    /// Text with characters outside the alphabet never matches.
+    pub fn matches(&self, text: &str) -> bool {
+        self.alphabet.encode(text).is_ok_and(|digits| self.check(&digits))
+    }
+
+    /// The first valid password after `text` with the same number of
+    /// characters, counting up like an odometer: "xz" is followed by "ya".
+    pub fn next(&self, text: &str) -> Result<Option<String>, String> {
+        let mut digits = self.alphabet.encode(text)?;
+        let excluded: Vec<u8> = self.policy.rules.iter()
+            .filter_map(|rule| match rule {
+                Rule::Without(excluded) => Some(excluded.clone()),
+                _ => None,
+            })
+            .flatten()
+            .collect();
+
+        loop {
+            if !increment(&mut digits, self.policy.base) {
+                return Ok(None);
+            }
+            // nothing with an excluded character in it can match, so move that
+            // character on and start everything after it again from the bottom
+            while let Some(position) = digits.iter().position(|digit| excluded.contains(digit)) {
+                digits[position + 1..].iter_mut().for_each(|digit| *digit = 0);
+                if !increment(&mut digits[..=position], self.policy.base) {
+                    return Ok(None);
+                }
+            }
+            if self.check(&digits) {
+                return Ok(Some(self.alphabet.decode(&digits)));
+            }
+        }
+    }
+}
+
+
+/// Add one to the digits, returning false when they wrap all the way round.
+fn increment(digits: &mut [u8], base: u32) -> bool {
+    for digit in digits.iter_mut().rev() {
+        if u32::from(*digit) + 1 < base {
+            *digit += 1;
+            return true;
+        }
+        *digit = 0;
+    }
+    false
+}
+
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_alphabet() {
+        let hex = Alphabet::hex();
+        assert_eq!(hex.encode("c0ffee"), Ok(vec![12, 0, 15, 15, 14, 14]));
+        assert_eq!(hex.decode(&[12, 0, 15, 15, 14, 14]), "c0ffee");
+        assert_eq!(hex.encode("c0g"), Err(String::from("'g' is not in the alphabet")));
+
+        assert_eq!(Alphabet::new("a"), Err(String::from("an alphabet needs 2 to 256 characters, not 1")));
+        assert_eq!(Alphabet::new("abca"), Err(String::from("'a' is in the alphabet twice")));
+        assert_eq!(Alphabet::lowercase().len(), 26);
+    }
+
+    #[test]
+    fn test_same_rules() {
+        // the numeric rules read the same over any alphabet
+        let sorted = TextPolicy::new(Alphabet::lowercase()).with(Rule::NonDecreasing).with(Rule::HasExactRun(2));
+        assert!(sorted.matches("aabcd"));
+        assert!(!sorted.matches("aaabc"));
+        assert!(!sorted.matches("aabdc"));
+        assert!(!sorted.matches("aab1"));
+
+        let hex = TextPolicy::new(Alphabet::hex()).with(Rule::NonDecreasing).with(Rule::HasExactRun(2));
+        assert!(hex.matches("11223"));
+        assert!(hex.matches("ccdef"));
+        assert!(!hex.matches("fe"));
+
+        // and agree with the numeric policy over decimal digits
+        let decimal = TextPolicy { alphabet: Alphabet::decimal(), policy: Policy::part2() };
+        for number in (100000..1000000).step_by(97) {
+            assert_eq!(decimal.matches(&number.to_string()), Policy::part2().matches(number));
+        }
+    }
+
+    #[test]
+    fn test_corporate() {
+        let policy = TextPolicy::corporate();
+        assert!(!policy.matches("hijklmmn"));
+        assert!(!policy.matches("abbceffg"));
+        assert!(!policy.matches("abbcegjk"));
+        assert!(policy.matches("abcdffaa"));
+        assert!(policy.matches("ghjaabcc"));
+
+        assert_eq!(policy.next("abcdefgh"), Ok(Some(String::from("abcdffaa"))));
+        assert_eq!(policy.next("ghijklmn"), Ok(Some(String::from("ghjaabcc"))));
+        assert_eq!(policy.next("ghjaabcc"), Ok(Some(String::from("ghjbbcdd"))));
+        assert_eq!(policy.next("zzzzzzzz"), Ok(None));
+        assert!(policy.next("Abcdefgh").is_err());
+    }
+
+    #[test]
+    fn test_next_counts_up() {
+        let any = TextPolicy::new(Alphabet::new("ab").unwrap());
+        assert_eq!(any.next("ab"), Ok(Some(String::from("ba"))));
+        assert_eq!(any.next("bb"), Ok(None));
+
+        let policy = TextPolicy::new(Alphabet::decimal()).without("13");
+        assert_eq!(policy.next("129"), Ok(Some(String::from("200"))));
+        assert_eq!(policy.next("099"), Ok(Some(String::from("200"))));
+    }
+}
@@ -0,0 +1,174 @@
+//! Answering "what do these two bodies both orbit" quickly.
+//!
+//! Walking up from both bodies works, but costs the depth of the map on every
+//! question. `Ancestry` does the walking once, storing for each body the
+//! body 1, 2, 4, 8, ... orbits above it (binary lifting). Climbing any number
+//! of orbits is then one jump per set bit, and the lowest common ancestor of
+//! two bodies takes O(log depth) jumps.
+
+use crate::orbits::{Body, OrbitMap};
+
+
+#[derive(Debug, Clone)]
+pub struct Ancestry {
+    depths: Vec<usize>,
+    /// `jumps[k][body]` is 2^k orbits above `body`, or the root if that's
+    /// further than the root.
+    jumps: Vec<Vec<Body>>,
+}
+
+
+impl Ancestry {
+    pub fn new(map: &OrbitMap) -> Ancestry {
+        let depths = map.depths();
+        let parents: Vec<Body> = (0..map.len()).map(|body| map.parent(body).unwrap_or(body)).collect();
+
+        let deepest = depths.iter().copied().max().unwrap_or(0);
+        let mut jumps = vec![parents];
+        while 1 << jumps.len() <= deepest {
+            let last = jumps.last().unwrap();
+            let next = (0..map.len()).map(|body| last[last[body]]).collect();
+            jumps.push(next);
+        }
+        Ancestry { depths, jumps }
+    }
+
+    /// How many orbits `body` is below the root.
+    pub fn depth(&self, body: Body) -> usize {
+        self.depths[body]
+    }
+
+    /// The body `steps` orbits above `body`, or None if that's past the root.
+    pub fn ancestor(&self, body: Body, steps: usize) -> Option<Body> {
+        if steps > self.depths[body] {
+            return None;
+        }
+        let mut body = body;
+        for (k, jumps) in self.jumps.iter().enumerate() {
+            if steps & (1 << k) != 0 {
+                body = jumps[body];
+            }
+        }
+        Some(body)
+    }
+
+    /// The deepest body that both `a` and `b` are, or orbit.
+    pub fn common_ancestor(&self, a: Body, b: Body) -> Body {
+        let (a, b) = if self.depths[a] >= self.depths[b] { (a, b) } else { (b, a) };
+        let mut a = self.ancestor(a, self.depths[a] - self.depths[b]).unwrap();
+        let mut b = b;
+        if a == b {
+            return a;
+        }
+        for jumps in self.jumps.iter().rev() {
+            if jumps[a] != jumps[b] {
+                a = jumps[a];
+                b = jumps[b];
+            }
+        }
+        self.jumps[0][a]
+    }
+
+    /// The number of orbits between `a` and `b`, going through their common
+    /// ancestor.
+    pub fn distance(&self, a: Body, b: Body) -> usize {
+        let common = self.common_ancestor(a, b);
+        self.depths[a] + self.depths[b] - 2 * self.depths[common]
+    }
+
+    /// Every body from `a` to `b`, both included.
+    pub fn path(&self, a: Body, b: Body) -> Vec<Body> {
+        let common = self.common_ancestor(a, b);
+        let climb = |from: Body| -> Vec<Body> {
+            let steps = self.depths[from] - self.depths[common];
+            let mut bodies = Vec::with_capacity(steps);
+            let mut body = from;
+            for _ in 0..steps {
+                bodies.push(body);
+                body = self.jumps[0][body];
+            }
+            bodies
+        };
+
+        let mut path = climb(a);
+        path.push(common);
+        path.extend(climb(b).into_iter().rev());
+        path
+    }
+
+    /// The orbital transfers needed to get from the body `from` orbits to
+    /// the body `to` orbits, or None if either of them is the root.
+    pub fn transfers(&self, from: Body, to: Body) -> Option<usize> {
+        let from = self.ancestor(from, 1)?;
+        let to = self.ancestor(to, 1)?;
+        Some(self.distance(from, to))
+    }
+}
+
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const EXAMPLE: &str = "COM)B\nB)C\nC)D\nD)E\nE)F\nB)G\nG)H\nD)I\nE)J\nJ)K\nK)L\nK)YOU\nI)SAN\n";
+
+    fn names(map: &OrbitMap, bodies: &[Body]) -> Vec<String> {
+        bodies.iter().map(|&body| map.name(body).to_string()).collect()
+    }
+
+    #[test]
+    fn test_transfers() {
+        let map = OrbitMap::parse(EXAMPLE).unwrap();
+        let ancestry = Ancestry::new(&map);
+        let body = |name| map.body(name).unwrap();
+
+        assert_eq!(ancestry.transfers(body("YOU"), body("SAN")), Some(4));
+        assert_eq!(ancestry.transfers(body("COM"), body("SAN")), None);
+        assert_eq!(names(&map, &ancestry.path(body("K"), body("I"))), vec!["K", "J", "E", "D", "I"]);
+    }
+
+    #[test]
+    fn test_queries() {
+        let map = OrbitMap::parse(EXAMPLE).unwrap();
+        let ancestry = Ancestry::new(&map);
+        let body = |name| map.body(name).unwrap();
+
+        assert_eq!(ancestry.depth(body("L")), 7);
+        assert_eq!(ancestry.ancestor(body("L"), 3), Some(body("E")));
+        assert_eq!(ancestry.ancestor(body("L"), 7), Some(body("COM")));
+        assert_eq!(ancestry.ancestor(body("L"), 8), None);
+
+        assert_eq!(ancestry.common_ancestor(body("H"), body("L")), body("B"));
+        assert_eq!(ancestry.common_ancestor(body("L"), body("E")), body("E"));
+        assert_eq!(ancestry.common_ancestor(body("F"), body("F")), body("F"));
+        assert_eq!(ancestry.distance(body("H"), body("L")), 8);
+        assert_eq!(names(&map, &ancestry.path(body("E"), body("E"))), vec!["E"]);
+        assert_eq!(names(&map, &ancestry.path(body("D"), body("L"))), vec!["D", "E", "J", "K", "L"]);
+    }
+
+    #[test]
+    fn test_agrees_with_walking() {
+        // a chain with branches off it every few bodies, checked against
+        // walking up the ancestors one at a time
+        let mut text = String::new();
+        for i in 0..2000 {
+            text += &format!("N{})N{}\n", i, i + 1);
+            if i % 7 == 0 {
+                text += &format!("N{})S{}\nS{})T{}\n", i, i, i, i);
+            }
+        }
+        let map = OrbitMap::parse(&text).unwrap();
+        let ancestry = Ancestry::new(&map);
+
+        let walking = |a: Body, b: Body| {
+            let above_a: Vec<Body> = map.ancestors(a).collect();
+            map.ancestors(b).find(|body| above_a.contains(body)).unwrap()
+        };
+        for (a, b) in [("T14", "T700"), ("N2000", "S0"), ("N1500", "N3"), ("T1995", "N1996")] {
+            let (a, b) = (map.body(a).unwrap(), map.body(b).unwrap());
+            assert_eq!(ancestry.common_ancestor(a, b), walking(a, b));
+            assert_eq!(ancestry.path(a, b).len(), ancestry.distance(a, b) + 1);
+        }
+    }
+}
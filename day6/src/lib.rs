@@ -0,0 +1,4 @@
+//! Orbit maps for day 6, shared between the binary and the tests.
+
+pub mod ancestry;
+pub mod orbits;
@@ -1,3 +1,46 @@
+use std::env;
+use std::fs::read_to_string;
+
+use day6::ancestry::Ancestry;
+use day6::orbits::OrbitMap;
+
+
 fn main() {
-    println!("Hello, world!");
+    /*
+    load the orbit map from the path given and check that it makes a single tree
+    then count every direct and indirect orbit in it
+    and the transfers needed to get from what YOU orbit to what SAN orbits, when both are on the map
+    */
+    let args: Vec<String> = env::args().collect();
+    if args.len() != 2 {
+        println!("Please input the path to an orbit map");
+        return
+    }
+
+    let text = match read_to_string(&args[1]) {
+        Ok(text) => text,
+        Err(err) => {
+            println!("failed to load input: {}", err);
+            return
+        }
+    };
+    let map = match OrbitMap::parse(&text) {
+        Ok(map) => map,
+        Err(err) => {
+            println!("bad orbit map: {}", err);
+            return
+        }
+    };
+
+    println!("{} bodies, all orbiting {}", map.len(), map.name(map.root()));
+    println!("part one answer: {}", map.checksum());
+
+    let ancestry = Ancestry::new(&map);
+    match (map.body("YOU"), map.body("SAN")) {
+        (Some(you), Some(san)) => match ancestry.transfers(you, san) {
+            Some(transfers) => println!("part two answer: {}", transfers),
+            None => println!("YOU or SAN is the root, so doesn't orbit anything"),
+        },
+        _ => println!("YOU and SAN aren't both on the map, so there's no part two"),
+    }
 }
@@ -0,0 +1,253 @@
+//! The orbit map: lines like `COM)B`, meaning B orbits COM.
+//!
+//! The bodies form a tree rooted at whatever orbits nothing (COM in the
+//! puzzle). Bodies are stored in an arena and referred to by index, with each
+//! name kept once. Parsing checks that the map really is a tree: every body
+//! orbits at most one other, exactly one body orbits nothing, and nothing
+//! ends up orbiting itself by going round a cycle.
+
+use std::collections::{HashMap, VecDeque};
+
+
+/// A body in an `OrbitMap`, by its index.
+pub type Body = usize;
+
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OrbitMap {
+    names: Vec<String>,
+    ids: HashMap<String, Body>,
+    parents: Vec<Option<Body>>,
+    /// The line each body's orbit was given on, for error messages.
+    lines: Vec<usize>,
+    children: Vec<Vec<Body>>,
+    root: Body,
+}
+
+
+impl OrbitMap {
+    pub fn parse(text: &str) -> Result<OrbitMap, String> {
+        let mut map = OrbitMap {
+            names: Vec::new(),
+            ids: HashMap::new(),
+            parents: Vec::new(),
+            lines: Vec::new(),
+            children: Vec::new(),
+            root: 0,
+        };
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (center, body) = match line.split_once(')') {
+                Some((center, body)) if is_name(center) && is_name(body) => (center, body),
+                _ => return Err(format!("line {}: expected an orbit like COM)B, not {:?}", number + 1, line)),
+            };
+
+            let center = map.intern(center);
+            let body = map.intern(body);
+            if let Some(parent) = map.parents[body] {
+                return Err(format!(
+                    "line {}: {} already orbits {} (line {})",
+                    number + 1, map.names[body], map.names[parent], map.lines[body],
+                ));
+            }
+            map.parents[body] = Some(center);
+            map.lines[body] = number + 1;
+            map.children[center].push(body);
+        }
+
+        let roots: Vec<Body> = (0..map.len()).filter(|&body| map.parents[body].is_none()).collect();
+        match roots[..] {
+            [root] => map.root = root,
+            [] if map.is_empty() => return Err(String::from("the map has no orbits")),
+            [] => return Err(map.cycle_error(0)),
+            _ => {
+                let names: Vec<&str> = roots.iter().map(|&root| map.name(root)).collect();
+                return Err(format!("the map has several roots: {}", names.join(", ")));
+            }
+        }
+
+        // with one parent each and a single root, anything the root doesn't
+        // reach is stuck in a cycle
+        let mut reached = vec![false; map.len()];
+        let mut stack = vec![map.root];
+        while let Some(body) = stack.pop() {
+            reached[body] = true;
+            stack.extend(&map.children[body]);
+        }
+        if let Some(body) = reached.iter().position(|&reached| !reached) {
+            return Err(map.cycle_error(body));
+        }
+        Ok(map)
+    }
+
+    fn intern(&mut self, name: &str) -> Body {
+        if let Some(&body) = self.ids.get(name) {
+            return body;
+        }
+        let body = self.names.len();
+        self.names.push(name.to_string());
+        self.ids.insert(name.to_string(), body);
+        self.parents.push(None);
+        self.lines.push(0);
+        self.children.push(Vec::new());
+        body
+    }
+
+    /// Describe the cycle `start` is in or leads into.
+    fn cycle_error(&self, start: Body) -> String {
+        let mut seen = vec![false; self.len()];
+        let mut body = start;
+        while !seen[body] {
+            seen[body] = true;
+            body = self.parents[body].expect("every body in a cycle has a parent");
+        }
+
+        let mut orbits = Vec::new();
+        let first = body;
+        loop {
+            let parent = self.parents[body].unwrap();
+            orbits.push((self.lines[body], format!("{}){} (line {})", self.name(parent), self.name(body), self.lines[body])));
+            body = parent;
+            if body == first {
+                break;
+            }
+        }
+        orbits.sort();
+        let orbits: Vec<String> = orbits.into_iter().map(|(_, orbit)| orbit).collect();
+        format!("the orbits go round in a cycle: {}", orbits.join(", "))
+    }
+
+    /// The number of bodies, including the root.
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// The body that orbits nothing.
+    pub fn root(&self) -> Body {
+        self.root
+    }
+
+    pub fn body(&self, name: &str) -> Option<Body> {
+        self.ids.get(name).copied()
+    }
+
+    pub fn name(&self, body: Body) -> &str {
+        &self.names[body]
+    }
+
+    /// What `body` directly orbits, or None for the root.
+    pub fn parent(&self, body: Body) -> Option<Body> {
+        self.parents[body]
+    }
+
+    /// The bodies directly orbiting `body`.
+    pub fn children(&self, body: Body) -> &[Body] {
+        &self.children[body]
+    }
+
+    /// `body` and everything it orbits, directly or not, up to the root.
+    /// `Ancestry` answers questions about these faster.
+    pub fn ancestors(&self, body: Body) -> impl Iterator<Item = Body> + '_ {
+        std::iter::successors(Some(body), move |&body| self.parents[body])
+    }
+
+    /// How many orbits away from the root each body is, indexed by body.
+    /// Worked out top down with a queue, so long chains don't use up the stack.
+    pub fn depths(&self) -> Vec<usize> {
+        let mut depths = vec![0; self.len()];
+        let mut queue = VecDeque::from(vec![self.root]);
+        while let Some(body) = queue.pop_front() {
+            for &child in &self.children[body] {
+                depths[child] = depths[body] + 1;
+                queue.push_back(child);
+            }
+        }
+        depths
+    }
+
+    /// The number of direct and indirect orbits: each body orbits everything
+    /// between it and the root, so that's the sum of the depths.
+    pub fn checksum(&self) -> usize {
+        self.depths().iter().sum()
+    }
+}
+
+
+fn is_name(text: &str) -> bool {
+    !text.is_empty() && text.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const EXAMPLE: &str = "COM)B\nB)C\nC)D\nD)E\nE)F\nB)G\nG)H\nD)I\nE)J\nJ)K\nK)L\n";
+
+    #[test]
+    fn test_parse() {
+        let map = OrbitMap::parse(EXAMPLE).unwrap();
+        assert_eq!(map.len(), 12);
+        assert_eq!(map.name(map.root()), "COM");
+
+        let b = map.body("B").unwrap();
+        let children: Vec<&str> = map.children(b).iter().map(|&child| map.name(child)).collect();
+        assert_eq!(children, vec!["C", "G"]);
+        assert_eq!(map.parent(b), Some(map.root()));
+        assert_eq!(map.parent(map.root()), None);
+        assert_eq!(map.body("Z"), None);
+
+        // the order of the lines doesn't matter, and blank ones are skipped
+        let shuffled = OrbitMap::parse("\r\nK)L\nCOM)B\n\nJ)K\nB)J\n").unwrap();
+        assert_eq!(shuffled.len(), 5);
+        assert_eq!(shuffled.name(shuffled.root()), "COM");
+    }
+
+    #[test]
+    fn test_checksum() {
+        let map = OrbitMap::parse(EXAMPLE).unwrap();
+        assert_eq!(map.checksum(), 42);
+
+        let depths = map.depths();
+        assert_eq!(depths[map.root()], 0);
+        assert_eq!(depths[map.body("D").unwrap()], 3);
+        assert_eq!(depths[map.body("L").unwrap()], 7);
+
+        assert_eq!(OrbitMap::parse("COM)B").unwrap().checksum(), 1);
+
+        let above: Vec<&str> = map.ancestors(map.body("H").unwrap()).map(|body| map.name(body)).collect();
+        assert_eq!(above, vec!["H", "G", "B", "COM"]);
+    }
+
+    #[test]
+    fn test_long_chain() {
+        // deep enough that recursing once per body would overflow the stack
+        let length = 200_000;
+        let text: String = (0..length).map(|i| format!("N{})N{}\n", i, i + 1)).collect();
+        let map = OrbitMap::parse(&text).unwrap();
+        assert_eq!(map.checksum(), length * (length + 1) / 2);
+    }
+
+    #[test]
+    fn test_errors() {
+        let parse = |text| OrbitMap::parse(text).unwrap_err();
+        assert_eq!(parse("COM)B\nB-C\n"), "line 2: expected an orbit like COM)B, not \"B-C\"");
+        assert_eq!(parse("COM)B\nB)\n"), "line 2: expected an orbit like COM)B, not \"B)\"");
+        assert_eq!(parse("COM)B\nB)C)D\n"), "line 2: expected an orbit like COM)B, not \"B)C)D\"");
+        assert_eq!(parse("COM)B\nB)C\nCOM)C\n"), "line 3: C already orbits B (line 2)");
+        assert_eq!(parse("COM)B\nX)Y\n"), "the map has several roots: COM, X");
+        assert_eq!(parse("A)B\nB)C\nC)A\n"), "the orbits go round in a cycle: A)B (line 1), B)C (line 2), C)A (line 3)");
+        assert_eq!(parse("COM)B\nC)D\nD)C\nD)E\n"), "the orbits go round in a cycle: C)D (line 2), D)C (line 3)");
+        assert_eq!(parse("COM)B\nA)A\n"), "the orbits go round in a cycle: A)A (line 2)");
+        assert_eq!(parse("\n\n"), "the map has no orbits");
+    }
+}
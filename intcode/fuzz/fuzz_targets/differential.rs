@@ -0,0 +1,56 @@
+//! Differential fuzzing of `Machine` against the reference interpreter.
+//!
+//! Run with `cargo +nightly fuzz run differential` from the intcode directory.
+//! The input bytes are read as little-endian 16-bit words: the first word is
+//! the number of inputs, those inputs follow, and the rest is the program.
+//! Opcode words are folded into mostly-valid instructions so the fuzzer
+//! spends its time executing rather than failing on the first word.
+
+#![no_main]
+
+use intcode::reference::compare;
+use intcode::Word;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let mut words: Vec<Word> = data.chunks(2)
+        .map(|pair| i16::from_le_bytes([pair[0], *pair.get(1).unwrap_or(&0)]) as Word)
+        .collect();
+    if words.is_empty() {
+        return;
+    }
+
+    let count = (words.remove(0).rem_euclid(4) as usize).min(words.len());
+    let input: Vec<Word> = words.drain(..count).collect();
+
+    let size = words.len().max(1) as Word;
+    let mut program = words;
+    let mut pc = 0;
+    while pc < program.len() {
+        // pick an opcode from the word's low bits and fold all three mode
+        // digits into valid modes, so writes get every mode too. One word in
+        // 32 keeps its digits as they are, to still reach the unknown modes.
+        let raw = program[pc];
+        let opcode = [1, 2, 3, 4, 5, 6, 7, 8, 9, 99, 1, 2][raw.rem_euclid(12) as usize];
+        let wild = (raw >> 10).rem_euclid(32) == 0;
+        let mode = |divisor: Word| {
+            let digit = raw.rem_euclid(divisor * 10) / divisor;
+            if wild { digit } else { digit % 3 }
+        };
+        program[pc] = mode(100) * 100 + mode(1000) * 1000 + mode(10000) * 10000 + opcode;
+        let arity = match opcode {
+            1 | 2 | 7 | 8 => 3,
+            5 | 6 => 2,
+            99 => 0,
+            _ => 1,
+        };
+        for param in program.iter_mut().skip(pc + 1).take(arity) {
+            *param = param.rem_euclid(size + 8) - 4;
+        }
+        pc += arity + 1;
+    }
+
+    if let Err(difference) = compare(&program, &input, 10_000) {
+        panic!("{:?} on {:?}: {}", program, input, difference);
+    }
+});
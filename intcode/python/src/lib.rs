@@ -0,0 +1,137 @@
+//! Python bindings for the Intcode machine.
+//!
+//! Build and install into the current virtualenv with `maturin develop`, then
+//! run the tests with `pytest`.
+//!
+//! ```python
+//! from pyintcode import Machine
+//!
+//! machine = Machine("3,9,8,9,10,9,4,9,99,-1,8")
+//! machine.push_input(8)
+//! machine.run()               # "halted"
+//! machine.drain_output()      # [1]
+//! ```
+//!
+//! Machine errors are raised as `pyintcode.IntcodeError`.
+
+use intcode::{load_program, parse_program, Status, Word};
+use pyo3::create_exception;
+use pyo3::exceptions::{PyException, PyValueError};
+use pyo3::prelude::*;
+
+create_exception!(pyintcode, IntcodeError, PyException);
+
+
+fn status(result: Result<Status, intcode::Error>) -> PyResult<&'static str> {
+    result.map(Status::name).map_err(|err| IntcodeError::new_err(err.to_string()))
+}
+
+
+#[pyclass(name = "Machine")]
+struct Machine {
+    machine: intcode::Machine,
+}
+
+#[pymethods]
+impl Machine {
+    /// Load a program from its comma separated source.
+    #[new]
+    fn new(source: &str) -> PyResult<Machine> {
+        let program = parse_program(source).map_err(PyValueError::new_err)?;
+        Ok(Machine { machine: intcode::Machine::new(program) })
+    }
+
+    #[staticmethod]
+    fn load(path: &str) -> PyResult<Machine> {
+        let program = load_program(path).map_err(PyValueError::new_err)?;
+        Ok(Machine { machine: intcode::Machine::new(program) })
+    }
+
+    fn push_input(&mut self, value: Word) {
+        self.machine.push_input(value);
+    }
+
+    /// Queue several inputs at once.
+    fn feed(&mut self, values: Vec<Word>) {
+        for value in values {
+            self.machine.push_input(value);
+        }
+    }
+
+    /// Queue a line of ASCII input, adding the newline.
+    fn push_line(&mut self, line: &str) {
+        self.machine.push_line(line);
+    }
+
+    /// Run until the program halts or needs input, or for at most
+    /// `max_steps` instructions if given.
+    #[pyo3(signature = (max_steps=None))]
+    fn run(&mut self, max_steps: Option<u64>) -> PyResult<&'static str> {
+        self.machine.set_fuel(max_steps);
+        let result = self.machine.run();
+        self.machine.set_fuel(None);
+        status(result)
+    }
+
+    fn step(&mut self) -> PyResult<&'static str> {
+        status(self.machine.step())
+    }
+
+    fn drain_output(&mut self) -> Vec<Word> {
+        self.machine.drain_output()
+    }
+
+    fn take_ascii(&mut self) -> String {
+        self.machine.take_ascii()
+    }
+
+    fn peek(&self, address: usize) -> Word {
+        self.machine.peek(address)
+    }
+
+    fn poke(&mut self, address: usize, value: Word) -> PyResult<()> {
+        self.machine.poke(address, value).map_err(|err| IntcodeError::new_err(err.to_string()))
+    }
+
+    /// A copy of the whole machine, queues included.
+    fn snapshot(&self) -> Machine {
+        Machine { machine: self.machine.clone() }
+    }
+
+    /// Go back to the state of an earlier `snapshot`.
+    fn restore(&mut self, snapshot: &Machine) {
+        self.machine = snapshot.machine.clone();
+    }
+
+    #[getter]
+    fn memory(&self) -> Vec<Word> {
+        self.machine.memory().to_vec()
+    }
+
+    #[getter]
+    fn pc(&self) -> usize {
+        self.machine.pc()
+    }
+
+    #[getter]
+    fn relative_base(&self) -> Word {
+        self.machine.relative_base()
+    }
+
+    #[getter]
+    fn halted(&self) -> bool {
+        self.machine.is_halted()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Machine(pc={}, memory={} words)", self.machine.pc(), self.machine.memory().len())
+    }
+}
+
+
+#[pymodule]
+fn pyintcode(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<Machine>()?;
+    module.add("IntcodeError", module.py().get_type::<IntcodeError>())?;
+    Ok(())
+}
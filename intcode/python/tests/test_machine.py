@@ -0,0 +1,88 @@
+"""The day5 examples, run through the Python bindings."""
+
+import pytest
+
+from pyintcode import IntcodeError, Machine
+
+
+def outputs(source, *inputs):
+    machine = Machine(source)
+    machine.feed(list(inputs))
+    assert machine.run() == "halted"
+    return machine.drain_output()
+
+
+def test_io():
+    assert outputs("3,0,4,0,99", 42) == [42]
+
+
+def test_modes():
+    machine = Machine("1002,4,3,4,33")
+    assert machine.run() == "halted"
+    assert machine.memory == [1002, 4, 3, 4, 99]
+    assert outputs("1101,100,-1,4,0") == []
+
+
+@pytest.mark.parametrize("source, below, equal, above", [
+    ("3,9,8,9,10,9,4,9,99,-1,8", 0, 1, 0),
+    ("3,9,7,9,10,9,4,9,99,-1,8", 1, 0, 0),
+    ("3,3,1108,-1,8,3,4,3,99", 0, 1, 0),
+    ("3,3,1107,-1,8,3,4,3,99", 1, 0, 0),
+])
+def test_comparisons(source, below, equal, above):
+    assert outputs(source, 7) == [below]
+    assert outputs(source, 8) == [equal]
+    assert outputs(source, 9) == [above]
+
+
+@pytest.mark.parametrize("source", [
+    "3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9",
+    "3,3,1105,-1,9,1101,0,0,12,4,12,99,1",
+])
+def test_jumps(source):
+    assert outputs(source, 0) == [0]
+    assert outputs(source, 5) == [1]
+
+
+def test_larger_example():
+    source = ("3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,"
+              "1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,"
+              "999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99")
+    assert outputs(source, 7) == [999]
+    assert outputs(source, 8) == [1000]
+    assert outputs(source, 9) == [1001]
+
+
+def test_pause_and_snapshot():
+    machine = Machine("3,0,4,0,99")
+    assert machine.run() == "needs-input"
+    snapshot = machine.snapshot()
+
+    machine.push_input(7)
+    assert machine.run() == "halted"
+    assert machine.drain_output() == [7]
+
+    machine.restore(snapshot)
+    assert not machine.halted
+    machine.push_input(8)
+    assert machine.run() == "halted"
+    assert machine.drain_output() == [8]
+
+
+def test_peek_poke_and_budget():
+    machine = Machine("1105,1,0")
+    assert machine.run(max_steps=100) == "budget-exhausted"
+    machine.poke(0, 99)
+    assert machine.peek(0) == 99
+    assert machine.run() == "halted"
+    with pytest.raises(IntcodeError):
+        machine.poke(2**64 - 1, 1)
+    with pytest.raises(IntcodeError):
+        machine.poke(10**12, 1)
+
+
+def test_errors():
+    with pytest.raises(IntcodeError):
+        Machine("42").run()
+    with pytest.raises(ValueError):
+        Machine("1,2,x")
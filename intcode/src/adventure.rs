@@ -0,0 +1,567 @@
+//! Harness for the ASCII text adventures (day 25's droid on Santa's ship).
+//!
+//! The game prints room descriptions like
+//!
+//! ```text
+//! == Hull Breach ==
+//! You got in through a hole in the floor here.
+//!
+//! Doors here lead:
+//! - north
+//! - west
+//!
+//! Items here:
+//! - mug
+//!
+//! Command?
+//! ```
+//!
+//! and reads one command per line. The `Explorer` maps the ship by walking
+//! every door, picks up every item that is not known to be deadly and then
+//! tries item combinations at the pressure-sensitive floor until one is
+//! accepted.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::{BufRead, Write};
+
+use crate::{Machine, Status};
+
+/// Instructions the game may run for a single command before it is taken to
+/// be stuck, e.g. after picking up the infinite loop.
+pub const COMMAND_BUDGET: u64 = 1_000_000;
+
+/// What `AsciiGame` fails with when a command uses up `COMMAND_BUDGET`.
+pub const OUT_OF_STEPS: &str = "the game ran out of steps";
+
+/// Items that end the game (or hang the machine) when picked up.
+pub const DEADLY_ITEMS: [&str; 5] = [
+    "infinite loop",
+    "giant electromagnet",
+    "molten lava",
+    "escape pod",
+    "photons",
+];
+
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Room {
+    pub name: String,
+    pub description: String,
+    pub doors: Vec<String>,
+    pub items: Vec<String>,
+}
+
+
+/// Parse every room description in `text`, in the order they were printed.
+///
+/// Moving onto the pressure-sensitive floor with the wrong weight prints two
+/// rooms: the floor itself and the checkpoint the droid is thrown back to.
+pub fn parse_rooms(text: &str) -> Vec<Room> {
+    let mut rooms = Vec::<Room>::new();
+    let mut list: Option<&str> = None;
+
+    for line in text.lines() {
+        let line = line.trim();
+
+        if line.starts_with("== ") && line.ends_with(" ==") && line.len() > 6 {
+            rooms.push(Room {
+                name: line[3..line.len() - 3].to_string(),
+                ..Room::default()
+            });
+            list = None;
+            continue;
+        }
+
+        let room = match rooms.last_mut() {
+            Some(room) => room,
+            None => continue,
+        };
+
+        if line == "Doors here lead:" {
+            list = Some("doors");
+        } else if line == "Items here:" {
+            list = Some("items");
+        } else if let Some(entry) = line.strip_prefix("- ") {
+            match list {
+                Some("doors") => room.doors.push(entry.to_string()),
+                Some("items") => room.items.push(entry.to_string()),
+                _ => {}
+            }
+        } else if line.is_empty() {
+            list = None;
+        } else if list.is_none() && room.doors.is_empty() && room.items.is_empty() {
+            if !room.description.is_empty() {
+                room.description.push('\n');
+            }
+            room.description.push_str(line);
+        }
+    }
+
+    rooms
+}
+
+
+/// The door that leads back, for the four doors the game knows about.
+pub fn opposite(direction: &str) -> Option<&'static str> {
+    match direction {
+        "north" => Some("south"),
+        "south" => Some("north"),
+        "east" => Some("west"),
+        "west" => Some("east"),
+        _ => None,
+    }
+}
+
+
+/// Pull the airlock code out of the final message.
+pub fn find_password(text: &str) -> Option<String> {
+    text.split_whitespace()
+        .map(|word| word.trim_matches(|c: char| !c.is_ascii_digit()))
+        .find(|word| word.len() > 3 && word.chars().all(|c| c.is_ascii_digit()))
+        .map(|word| word.to_string())
+}
+
+
+/// Anything that can be played by sending text commands.
+pub trait Game {
+    /// Run until the game wants a command and return what it printed.
+    fn output(&mut self) -> Result<String, String>;
+
+    /// Send a single command (without the newline) and return the reply.
+    fn command(&mut self, command: &str) -> Result<String, String>;
+
+    /// Whether the game has finished and won't take any more commands.
+    fn is_over(&self) -> bool {
+        false
+    }
+}
+
+
+/// A `Game` backed by an Intcode machine.
+#[derive(Debug, Clone)]
+pub struct AsciiGame {
+    pub machine: Machine,
+    /// Instructions allowed per command, `COMMAND_BUDGET` unless changed.
+    pub budget: Option<u64>,
+}
+
+impl AsciiGame {
+    pub fn new(program: Vec<crate::Word>) -> AsciiGame {
+        AsciiGame { machine: Machine::new(program), budget: Some(COMMAND_BUDGET) }
+    }
+}
+
+impl Game for AsciiGame {
+    fn output(&mut self) -> Result<String, String> {
+        self.machine.set_fuel(self.budget);
+        if self.machine.run().map_err(|err| err.to_string())? == Status::BudgetExhausted {
+            return Err(String::from(OUT_OF_STEPS));
+        }
+        Ok(self.machine.take_ascii())
+    }
+
+    fn command(&mut self, command: &str) -> Result<String, String> {
+        if self.machine.is_halted() {
+            return Err(String::from("the game has already ended"));
+        }
+        self.machine.push_line(command);
+        self.output()
+    }
+
+    fn is_over(&self) -> bool {
+        self.machine.is_halted()
+    }
+}
+
+
+pub struct Explorer<G: Game> {
+    pub game: G,
+    pub deadly: HashSet<String>,
+    pub rooms: HashMap<String, Room>,
+    /// (room, door) -> the room on the other side
+    pub links: HashMap<(String, String), String>,
+    pub inventory: Vec<String>,
+    /// The checkpoint room and the door that leads onto the pressure plate.
+    pub checkpoint: Option<(String, String)>,
+    /// (room, door) for doors that aren't a compass direction. They're left
+    /// alone, since there would be no knowing how to get back.
+    pub unknown_doors: Vec<(String, String)>,
+    here: String,
+}
+
+impl<G: Game + Clone> Explorer<G> {
+    pub fn new(game: G) -> Explorer<G> {
+        Explorer {
+            game,
+            deadly: DEADLY_ITEMS.iter().map(|item| item.to_string()).collect(),
+            rooms: HashMap::new(),
+            links: HashMap::new(),
+            inventory: Vec::new(),
+            checkpoint: None,
+            unknown_doors: Vec::new(),
+            here: String::new(),
+        }
+    }
+
+    /// Walk through every door on the ship, picking up every safe item, and
+    /// finish back in the starting room.
+    pub fn explore(&mut self) -> Result<(), String> {
+        let text = self.game.output()?;
+        let room = parse_rooms(&text).pop().ok_or("no room in the opening text")?;
+        let start = room.name.clone();
+        self.enter(room)?;
+        self.explore_from(&start)?;
+        self.here = start;
+        Ok(())
+    }
+
+    fn enter(&mut self, room: Room) -> Result<(), String> {
+        for item in &room.items {
+            if self.deadly.contains(item) {
+                continue;
+            }
+            // an item that hangs the game is as bad as the known ones, so go
+            // back to before it was picked up and leave it where it is
+            let before = self.game.clone();
+            let reply = match self.game.command(&format!("take {}", item)) {
+                Err(err) if err == OUT_OF_STEPS => {
+                    self.game = before;
+                    self.deadly.insert(item.clone());
+                    continue;
+                }
+                reply => reply?,
+            };
+            if !reply.contains("You take") {
+                return Err(format!("could not take {}: {}", item, reply.trim()));
+            }
+            self.inventory.push(item.clone());
+        }
+        self.rooms.insert(room.name.clone(), room);
+        Ok(())
+    }
+
+    fn explore_from(&mut self, here: &str) -> Result<(), String> {
+        let doors = self.rooms[here].doors.clone();
+
+        for door in doors {
+            let key = (here.to_string(), door.clone());
+            if self.links.contains_key(&key) {
+                continue;
+            }
+            let back = match opposite(&door) {
+                Some(back) => back,
+                None => {
+                    self.unknown_doors.push(key);
+                    continue;
+                }
+            };
+
+            let reply = self.game.command(&door)?;
+            let mut rooms = parse_rooms(&reply);
+            if rooms.is_empty() {
+                return Err(format!("moving {} from {} went nowhere: {}", door, here, reply.trim()));
+            }
+            let next = rooms.remove(0);
+            self.links.insert(key, next.name.clone());
+
+            if rooms.last().is_some_and(|room| room.name == here) {
+                // thrown back by the pressure plate; nothing else to learn here yet
+                self.checkpoint = Some((here.to_string(), door));
+                self.rooms.entry(next.name.clone()).or_insert(next);
+                continue;
+            }
+
+            self.links.insert((next.name.clone(), back.to_string()), here.to_string());
+            if self.rooms.contains_key(&next.name) {
+                self.game.command(back)?;
+                continue;
+            }
+
+            let name = next.name.clone();
+            self.enter(next)?;
+            self.explore_from(&name)?;
+            self.game.command(back)?;
+        }
+
+        Ok(())
+    }
+
+    /// Shortest list of doors to walk through to get from `from` to `to`.
+    pub fn route(&self, from: &str, to: &str) -> Option<Vec<String>> {
+        let mut previous = HashMap::<String, (String, String)>::new();
+        let mut queue = VecDeque::new();
+        queue.push_back(from.to_string());
+
+        while let Some(room) = queue.pop_front() {
+            if room == to {
+                let mut path = Vec::new();
+                let mut room = room;
+                while let Some((before, door)) = previous.get(&room) {
+                    path.push(door.clone());
+                    room = before.clone();
+                }
+                path.reverse();
+                return Some(path);
+            }
+            for ((source, door), target) in &self.links {
+                if source == &room && target != from && !previous.contains_key(target) {
+                    previous.insert(target.clone(), (room.clone(), door.clone()));
+                    queue.push_back(target.clone());
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Walk to the checkpoint and try every subset of the inventory on the
+    /// pressure plate. Returns the final message from the game.
+    pub fn solve_checkpoint(&mut self) -> Result<String, String> {
+        let (checkpoint, door) = self.checkpoint.clone().ok_or("the checkpoint was never found")?;
+        let route = self.route(&self.here, &checkpoint).ok_or("no route to the checkpoint")?;
+        for step in route {
+            self.game.command(&step)?;
+        }
+        self.here = checkpoint;
+
+        // walk the subsets in Gray code order so each attempt only changes one item
+        let items = self.inventory.clone();
+        let attempts = 1u64.checked_shl(items.len() as u32).filter(|_| items.len() < 64)
+            .ok_or_else(|| format!("{} items are too many to try every combination of", items.len()))?;
+        let mut holding = vec![true; items.len()];
+        for attempt in 0..attempts {
+            let gray = attempt ^ (attempt >> 1);
+            for (i, item) in items.iter().enumerate() {
+                let wanted = gray & (1 << i) == 0;
+                if wanted != holding[i] {
+                    let verb = if wanted { "take" } else { "drop" };
+                    self.game.command(&format!("{} {}", verb, item))?;
+                    holding[i] = wanted;
+                }
+            }
+
+            let reply = self.game.command(&door)?;
+            if !reply.contains("Alert!") {
+                self.inventory = items.iter().zip(&holding)
+                    .filter(|(_, &held)| held)
+                    .map(|(item, _)| item.clone())
+                    .collect();
+                return Ok(reply);
+            }
+        }
+
+        Err(String::from("no combination of items got past the checkpoint"))
+    }
+}
+
+
+/// Let a human play. Besides the game's own commands this understands
+/// `save <name>`, `restore <name>` and `quit`.
+pub fn play<G: Game + Clone, R: BufRead, W: Write>(game: G, input: R, output: &mut W) -> Result<(), String> {
+    let mut game = game;
+    let mut snapshots = HashMap::<String, G>::new();
+
+    let text = game.output()?;
+    write!(output, "{}", text).map_err(|err| err.to_string())?;
+
+    for line in input.lines() {
+        let line = line.map_err(|err| err.to_string())?;
+        let line = line.trim();
+
+        let reply = if let Some(name) = line.strip_prefix("save ") {
+            snapshots.insert(name.to_string(), game.clone());
+            format!("Saved {}.\n\nCommand?\n", name)
+        } else if let Some(name) = line.strip_prefix("restore ") {
+            match snapshots.get(name) {
+                Some(snapshot) => {
+                    game = snapshot.clone();
+                    format!("Restored {}.\n\nCommand?\n", name)
+                }
+                None => format!("No snapshot called {}.\n\nCommand?\n", name),
+            }
+        } else if line == "quit" {
+            break;
+        } else {
+            game.command(line)?
+        };
+
+        write!(output, "{}", reply).map_err(|err| err.to_string())?;
+        if game.is_over() {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HULL_BREACH: &str = "\n\n\n== Hull Breach ==\nYou got in through a hole in the floor here.\n\nDoors here lead:\n- north\n- west\n\nItems here:\n- mug\n- spool of cat6\n\nCommand?\n";
+
+    #[test]
+    fn test_parse_rooms() {
+        let rooms = parse_rooms(HULL_BREACH);
+        assert_eq!(rooms, vec![Room {
+            name: String::from("Hull Breach"),
+            description: String::from("You got in through a hole in the floor here."),
+            doors: vec![String::from("north"), String::from("west")],
+            items: vec![String::from("mug"), String::from("spool of cat6")],
+        }]);
+
+        let ejected = "== Pressure-Sensitive Floor ==\nAnalyzing...\n\nDoors here lead:\n- south\n\nA loud, robotic voice says \"Alert! Droids on this ship are heavier than the detected value!\" and you are ejected back to the checkpoint.\n\n== Security Checkpoint ==\nIn the next room, a pressure-sensitive floor will verify your identity.\n\nDoors here lead:\n- north\n- east\n\nCommand?\n";
+        let names: Vec<String> = parse_rooms(ejected).into_iter().map(|room| room.name).collect();
+        assert_eq!(names, vec!["Pressure-Sensitive Floor", "Security Checkpoint"]);
+    }
+
+    #[test]
+    fn test_opposite() {
+        assert_eq!(opposite("north"), Some("south"));
+        assert_eq!(opposite("west"), Some("east"));
+        assert_eq!(opposite("up"), None);
+    }
+
+    #[test]
+    fn test_find_password() {
+        let text = "\"Oh, hello! You should be able to get in by typing 2622472 on the keypad at the main airlock.\"";
+        assert_eq!(find_password(text), Some(String::from("2622472")));
+        assert_eq!(find_password("Command?"), None);
+    }
+
+    /// A tiny ship: the checkpoint only lets you through holding the mug and
+    /// the cat6, and picking up the strange loop hangs it.
+    #[derive(Clone)]
+    struct FakeShip {
+        here: &'static str,
+        floor: HashMap<&'static str, Vec<String>>,
+        inventory: Vec<String>,
+        stuck: bool,
+    }
+
+    impl FakeShip {
+        fn new() -> FakeShip {
+            let mut floor = HashMap::new();
+            floor.insert("Hull Breach", vec![String::from("mug"), String::from("spool of cat6")]);
+            floor.insert("Kitchen", vec![String::from("infinite loop"), String::from("food ration"), String::from("strange loop")]);
+            FakeShip { here: "Hull Breach", floor, inventory: Vec::new(), stuck: false }
+        }
+
+        fn doors(room: &str) -> Vec<(&'static str, &'static str)> {
+            match room {
+                "Hull Breach" => vec![("north", "Kitchen"), ("west", "Security Checkpoint")],
+                "Kitchen" => vec![("south", "Hull Breach"), ("hatch", "Crawlspace")],
+                "Security Checkpoint" => vec![("north", "Pressure-Sensitive Floor"), ("east", "Hull Breach")],
+                _ => vec![],
+            }
+        }
+
+        fn describe(&self, room: &str) -> String {
+            let mut text = format!("\n== {} ==\nA room.\n\nDoors here lead:\n", room);
+            for (door, _) in FakeShip::doors(room) {
+                text.push_str(&format!("- {}\n", door));
+            }
+            if let Some(items) = self.floor.get(room).filter(|items| !items.is_empty()) {
+                text.push_str("\nItems here:\n");
+                for item in items {
+                    text.push_str(&format!("- {}\n", item));
+                }
+            }
+            text
+        }
+    }
+
+    impl Game for FakeShip {
+        fn output(&mut self) -> Result<String, String> {
+            Ok(self.describe(self.here) + "\nCommand?\n")
+        }
+
+        fn command(&mut self, command: &str) -> Result<String, String> {
+            if self.stuck || command == "take strange loop" {
+                self.stuck = true;
+                return Err(String::from(OUT_OF_STEPS));
+            }
+            if let Some(item) = command.strip_prefix("take ") {
+                assert!(!DEADLY_ITEMS.contains(&item), "picked up {}", item);
+                let items = self.floor.get_mut(self.here).unwrap();
+                let index = items.iter().position(|i| i == item).unwrap();
+                self.inventory.push(items.remove(index));
+                return Ok(format!("\nYou take the {}.\n\nCommand?\n", item));
+            }
+            if let Some(item) = command.strip_prefix("drop ") {
+                let index = self.inventory.iter().position(|i| i == item).unwrap();
+                let item = self.inventory.remove(index);
+                self.floor.entry(self.here).or_default().push(item.clone());
+                return Ok(format!("\nYou drop the {}.\n\nCommand?\n", item));
+            }
+
+            let (_, target) = FakeShip::doors(self.here).into_iter()
+                .find(|(door, _)| *door == command)
+                .ok_or(format!("bad command {}", command))?;
+
+            if target == "Pressure-Sensitive Floor" {
+                let mut held = self.inventory.clone();
+                held.sort();
+                let text = self.describe(target);
+                if held == vec!["mug", "spool of cat6"] {
+                    return Ok(text + "\nYou should be able to get in by typing 1234 on the keypad at the main airlock.\n");
+                }
+                return Ok(text + "\nA loud, robotic voice says \"Alert! Droids on this ship are heavier than the detected value!\" and you are ejected back to the checkpoint.\n" + &self.describe(self.here) + "\nCommand?\n");
+            }
+
+            self.here = target;
+            Ok(self.describe(target) + "\nCommand?\n")
+        }
+    }
+
+    #[test]
+    fn test_explore_and_solve() {
+        let mut explorer = Explorer::new(FakeShip::new());
+        explorer.explore().unwrap();
+
+        assert_eq!(explorer.rooms.len(), 4);
+        assert_eq!(explorer.checkpoint, Some((String::from("Security Checkpoint"), String::from("north"))));
+        assert_eq!(explorer.unknown_doors, vec![(String::from("Kitchen"), String::from("hatch"))]);
+        let mut inventory = explorer.inventory.clone();
+        inventory.sort();
+        assert_eq!(inventory, vec!["food ration", "mug", "spool of cat6"]);
+        assert!(explorer.deadly.contains("strange loop"));
+
+        let reply = explorer.solve_checkpoint().unwrap();
+        assert_eq!(find_password(&reply), Some(String::from("1234")));
+    }
+
+    #[test]
+    fn test_too_many_items() {
+        let mut explorer = Explorer::new(FakeShip::new());
+        explorer.here = String::from("Security Checkpoint");
+        explorer.checkpoint = Some((String::from("Security Checkpoint"), String::from("north")));
+        explorer.inventory = (0..64).map(|i| format!("item {}", i)).collect();
+        assert_eq!(explorer.solve_checkpoint().unwrap_err(), "64 items are too many to try every combination of");
+    }
+
+    #[test]
+    fn test_budget() {
+        // jumps to itself forever
+        let mut game = AsciiGame::new(vec![1105, 1, 0]);
+        game.budget = Some(100);
+        assert_eq!(game.output(), Err(String::from(OUT_OF_STEPS)));
+        assert!(!game.is_over());
+    }
+
+    #[test]
+    fn test_play() {
+        let script = "north\nsave kitchen\nsouth\nrestore kitchen\ntake food ration\nrestore pantry\nquit\nnorth\n";
+        let mut output = Vec::new();
+        play(FakeShip::new(), script.as_bytes(), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        // the food ration is only in the kitchen, so taking it shows the restore worked
+        assert!(output.starts_with("\n== Hull Breach =="));
+        assert!(output.contains("Saved kitchen.") && output.contains("Restored kitchen."));
+        assert!(output.contains("You take the food ration."));
+        assert!(output.ends_with("No snapshot called pantry.\n\nCommand?\n"));
+        assert_eq!(output.matches("== Kitchen ==").count(), 1);
+    }
+}
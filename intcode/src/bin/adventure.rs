@@ -0,0 +1,50 @@
+use std::env;
+use std::io;
+
+use intcode::adventure::{find_password, play, AsciiGame, Explorer};
+use intcode::load_program;
+
+
+fn main() {
+    /*
+    play the day 25 adventure from the program at the given path
+    by default the ship is explored and the checkpoint solved automatically,
+    with --manual the game is handed over to stdin instead
+    */
+    let args: Vec<String> = env::args().collect();
+    let manual = args.iter().any(|arg| arg == "--manual");
+    let paths: Vec<&String> = args.iter().skip(1).filter(|arg| !arg.starts_with("--")).collect();
+    if paths.len() != 1 {
+        println!("Please input a single path to the file containing the input data (and optionally --manual)");
+        return
+    }
+
+    let program = match load_program(paths[0]) {
+        Ok(program) => program,
+        Err(err) => {
+            println!("failed to load input: {}", err);
+            return
+        }
+    };
+
+    if manual {
+        let stdin = io::stdin();
+        if let Err(err) = play(AsciiGame::new(program), stdin.lock(), &mut io::stdout()) {
+            println!("the game stopped: {}", err);
+        }
+        return
+    }
+
+    let mut explorer = Explorer::new(AsciiGame::new(program));
+    let result = explorer.explore().and_then(|_| explorer.solve_checkpoint());
+    match result {
+        Ok(reply) => {
+            println!("explored {} rooms, carrying {:?}", explorer.rooms.len(), explorer.inventory);
+            match find_password(&reply) {
+                Some(password) => println!("password: {}", password),
+                None => println!("{}", reply.trim()),
+            }
+        }
+        Err(err) => println!("failed: {}", err),
+    }
+}
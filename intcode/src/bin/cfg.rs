@@ -0,0 +1,29 @@
+use std::env;
+
+use intcode::cfg::Cfg;
+use intcode::load_program;
+
+
+fn main() {
+    /*
+    print the control-flow graph of the program at the given path in graphviz format,
+    with a summary of what couldn't be reached on stderr
+    e.g. `cargo run --bin cfg ../day5/input.txt | dot -Tsvg > day5.svg`
+    */
+    let args: Vec<String> = env::args().collect();
+    if args.len() != 2 {
+        println!("Please input a single path to the file containing the input data");
+        return
+    }
+
+    match load_program(&args[1]) {
+        Ok(program) => {
+            let cfg = Cfg::build(&program);
+            print!("{}", cfg.to_dot());
+            eprintln!("blocks: {}, functions: {}", cfg.blocks.len(), cfg.functions.len());
+            eprintln!("data: {:?}", cfg.data);
+            eprintln!("unreachable: {:?}", cfg.unreachable);
+        }
+        Err(err) => println!("failed to load input: {}", err),
+    }
+}
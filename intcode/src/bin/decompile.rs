@@ -0,0 +1,48 @@
+use std::env;
+
+use intcode::decompile::{decompile, traced_image};
+use intcode::{load_program, Word};
+
+
+fn main() {
+    /*
+    print pseudo-code for the program at the given path
+    any further arguments are fed to the program as input and it is traced first,
+    so that code it patches at runtime (like day5's) is decompiled as it ran
+    e.g. `cargo run --bin decompile ../day5/input.txt 5`
+    */
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 2 {
+        println!("Please input a path to the file containing the input data, followed by any inputs");
+        return
+    }
+
+    let program = match load_program(&args[1]) {
+        Ok(program) => program,
+        Err(err) => {
+            println!("failed to load input: {}", err);
+            return
+        }
+    };
+
+    let mut inputs = Vec::<Word>::new();
+    for arg in &args[2..] {
+        match arg.parse() {
+            Ok(value) => inputs.push(value),
+            Err(_) => {
+                println!("inputs must be numbers, not {}", arg);
+                return
+            }
+        }
+    }
+
+    if inputs.is_empty() {
+        print!("{}", decompile(&program));
+        return
+    }
+
+    match traced_image(&program, &inputs) {
+        Ok(image) => print!("{}", decompile(&image)),
+        Err(err) => println!("the traced run failed: {}", err),
+    }
+}
@@ -0,0 +1,461 @@
+use std::fs::read_to_string;
+use std::io::{self, BufRead, Write};
+use std::process::exit;
+use std::time::{Duration, Instant};
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+
+use intcode::cfg::Cfg;
+use intcode::format::{self, IoMode, ProgramFile};
+use intcode::instruction::Instruction;
+use intcode::patch::{Patches, Symbols};
+use intcode::{Machine, Status, Word};
+
+// exit codes, besides 0 for a program that halted
+const EXIT_ERROR: i32 = 1;
+// the same as clap uses for arguments it can't parse
+const EXIT_USAGE: i32 = 2;
+const EXIT_NEEDS_INPUT: i32 = 3;
+const EXIT_BUDGET_EXHAUSTED: i32 = 4;
+const EXIT_LOAD_FAILED: i32 = 5;
+const EXIT_NOT_FOUND: i32 = 6;
+
+
+/// Run and inspect Intcode programs.
+///
+/// Exit codes: 0 the program halted, 1 the machine stopped on an error, 2 bad
+/// arguments, 3 the program needs more input, 4 the step budget or timeout
+/// ran out, 5 the program or input file couldn't be loaded, 6 `search` found
+/// nothing.
+#[derive(Parser)]
+#[command(name = "intcode")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run a program until it halts
+    Run(RunArgs),
+    /// Overwrite words before running, as day2 does, and print a word afterwards
+    Patch {
+        #[command(flatten)]
+        run: RunArgs,
+        /// Words to overwrite, the same as --patch, e.g. `--set 1=12,2=2`
+        #[arg(short, long, value_name = "SPEC", required = true)]
+        set: Vec<String>,
+        /// The address to print once the program halts
+        #[arg(long, default_value_t = 0)]
+        show: usize,
+    },
+    /// Find the noun and verb that leave the target in address 0, as day2 does
+    Search {
+        path: String,
+        #[arg(long, default_value_t = 19690720)]
+        target: Word,
+        /// Largest noun and verb to try
+        #[arg(long, default_value_t = 99)]
+        max: Word,
+        /// Instructions allowed per attempt
+        #[arg(long, default_value_t = 100_000)]
+        max_steps: u64,
+    },
+    /// Print the program's recovered code and data
+    Disasm {
+        path: String,
+    },
+    /// Run a program, printing every instruction as it executes
+    Trace(RunArgs),
+    /// Time repeated runs of a program
+    Bench {
+        #[command(flatten)]
+        run: RunArgs,
+        #[arg(short = 'n', long, default_value_t = 100, value_parser = clap::value_parser!(u32).range(1..))]
+        iterations: u32,
+    },
+}
+
+
+#[derive(Args)]
+struct RunArgs {
+    /// The program, in text or binary format
+    path: String,
+    /// Inputs to queue before running, e.g. `--input 1,5`
+    #[arg(short, long, value_delimiter = ',', allow_hyphen_values = true)]
+    input: Vec<Word>,
+    /// Queue inputs from a file: numbers, or text with --ascii
+    #[arg(long, value_name = "PATH")]
+    input_file: Option<String>,
+    /// Ask on stdin for more input whenever the program runs out
+    #[arg(short = 'I', long)]
+    interactive: bool,
+    /// Treat input files, interactive input and output as text (the default
+    /// for programs whose header says `io: ascii`)
+    #[arg(long)]
+    ascii: bool,
+    /// How to print the output, by default lines or ascii
+    #[arg(short, long, value_enum)]
+    output: Option<OutputFormat>,
+    /// Words to overwrite before running, e.g. `1=12,2=2`, `10..20=0` or
+    /// `noun=12` with --symbols
+    #[arg(short, long, value_name = "SPEC")]
+    patch: Vec<String>,
+    /// Symbol file for the names used in patches, one `name address` a line
+    #[arg(long, value_name = "PATH")]
+    symbols: Option<String>,
+    /// Describe the run on stderr afterwards: program, patches, inputs and outcome
+    #[arg(long)]
+    report: bool,
+    /// Stop after this many instructions
+    #[arg(long)]
+    max_steps: Option<u64>,
+    /// Stop after this many seconds
+    #[arg(long, value_name = "SECONDS", value_parser = parse_timeout)]
+    timeout: Option<Duration>,
+}
+
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    /// One value per line
+    Lines,
+    /// Comma separated on one line
+    Csv,
+    /// Values as characters
+    Ascii,
+    /// Nothing at all
+    Quiet,
+}
+
+
+fn fail(code: i32, message: String) -> ! {
+    eprintln!("{}", message);
+    exit(code)
+}
+
+
+fn load(path: &str) -> ProgramFile {
+    format::load(path).unwrap_or_else(|err| fail(EXIT_LOAD_FAILED, format!("failed to load {}: {}", path, err)))
+}
+
+
+fn parse_timeout(text: &str) -> Result<Duration, String> {
+    let seconds = text.parse::<f64>().map_err(|_| format!("{:?} is not a number of seconds", text))?;
+    Duration::try_from_secs_f64(seconds).map_err(|_| format!("{} is not a usable number of seconds", text))
+}
+
+
+fn parse_numbers(text: &str) -> Result<Vec<Word>, String> {
+    text.split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.parse::<Word>().map_err(|_| format!("inputs must be numbers, not {}", word)))
+        .collect()
+}
+
+
+impl RunArgs {
+    fn is_ascii(&self, file: &ProgramFile) -> bool {
+        self.ascii || file.header.io == Some(IoMode::Ascii)
+    }
+
+    fn output_format(&self, file: &ProgramFile) -> OutputFormat {
+        self.output.unwrap_or(if self.is_ascii(file) { OutputFormat::Ascii } else { OutputFormat::Lines })
+    }
+
+    fn patches(&self, file: &ProgramFile) -> Patches {
+        let symbols = match &self.symbols {
+            Some(path) => read_to_string(path)
+                .map_err(|err| err.to_string())
+                .and_then(|text| Symbols::parse(&text))
+                .unwrap_or_else(|err| fail(EXIT_LOAD_FAILED, format!("failed to load {}: {}", path, err))),
+            None => Symbols::new(),
+        };
+        Patches::parse(&self.patch.join(","), &symbols, file.memory_size())
+            .unwrap_or_else(|err| fail(EXIT_USAGE, format!("bad patch: {}", err)))
+    }
+
+    /// A machine with `patches` applied and the inputs and limits from the
+    /// command line.
+    fn machine(&self, file: &ProgramFile, patches: &Patches) -> Machine {
+        let mut machine = file.machine();
+        patches.apply(&mut machine)
+            .unwrap_or_else(|err| fail(EXIT_USAGE, format!("bad patch: {}", err)));
+        for &value in &self.input {
+            machine.push_input(value);
+        }
+
+        if let Some(path) = &self.input_file {
+            let text = read_to_string(path)
+                .unwrap_or_else(|err| fail(EXIT_LOAD_FAILED, format!("failed to load {}: {}", path, err)));
+            if self.is_ascii(file) {
+                for byte in text.bytes() {
+                    machine.push_input(Word::from(byte));
+                }
+            } else {
+                for value in parse_numbers(&text).unwrap_or_else(|err| fail(EXIT_LOAD_FAILED, err)) {
+                    machine.push_input(value);
+                }
+            }
+        }
+
+        machine.set_fuel(self.max_steps);
+        machine
+    }
+
+    /// None for no timeout, or one too long to tell from none.
+    fn deadline(&self) -> Option<Instant> {
+        self.timeout.and_then(|timeout| Instant::now().checked_add(timeout))
+    }
+
+    /// Queue a line from stdin and return what was queued, or None at the
+    /// end of stdin.
+    fn read_input(&self, file: &ProgramFile, machine: &mut Machine) -> Option<Vec<Word>> {
+        eprint!("input> ");
+        io::stderr().flush().unwrap();
+        let mut line = String::new();
+        if io::stdin().lock().read_line(&mut line).unwrap_or(0) == 0 {
+            return None
+        }
+
+        let line = line.trim_end_matches(['\r', '\n']);
+        let values = if self.is_ascii(file) {
+            line.bytes().chain(Some(b'\n')).map(Word::from).collect()
+        } else {
+            parse_numbers(line).unwrap_or_else(|err| {
+                eprintln!("{}", err);
+                Vec::new()
+            })
+        };
+        values.iter().for_each(|&value| machine.push_input(value));
+        Some(values)
+    }
+}
+
+
+fn print_output(format: OutputFormat, machine: &mut Machine) {
+    match format {
+        OutputFormat::Lines => machine.drain_output().iter().for_each(|value| println!("{}", value)),
+        OutputFormat::Csv => {
+            let values: Vec<String> = machine.drain_output().iter().map(|value| value.to_string()).collect();
+            if !values.is_empty() {
+                println!("{}", values.join(","));
+            }
+        }
+        OutputFormat::Ascii => print!("{}", machine.take_ascii()),
+        OutputFormat::Quiet => {
+            machine.drain_output();
+        }
+    }
+    io::stdout().flush().unwrap();
+}
+
+
+/// Map how a run ended to an exit code, explaining anything but a halt.
+fn finish(result: Result<Status, intcode::Error>) -> i32 {
+    match result {
+        Ok(Status::Halted) => 0,
+        Ok(Status::NeedsInput) => {
+            eprintln!("the program needs more input");
+            EXIT_NEEDS_INPUT
+        }
+        Ok(Status::BudgetExhausted) | Ok(Status::Running) => {
+            eprintln!("the program ran out of steps or time");
+            EXIT_BUDGET_EXHAUSTED
+        }
+        Err(err) => {
+            eprintln!("the program stopped: {}", err);
+            EXIT_ERROR
+        }
+    }
+}
+
+
+/// Enough to repeat the run: the patches are written with symbols resolved,
+/// and anything typed in is listed after the inputs given up front.
+fn report(args: &RunArgs, file: &ProgramFile, patches: &Patches, machine: &Machine, typed: &[Word], result: &Result<Status, intcode::Error>) {
+    let join = |values: &[Word]| values.iter().map(|value| value.to_string()).collect::<Vec<String>>().join(",");
+    eprintln!("program       {}", args.path);
+    if let Some(name) = &file.header.name {
+        eprintln!("name          {}", name);
+    }
+    if !patches.is_empty() {
+        eprintln!("patches       {}", patches);
+    }
+    eprintln!("inputs        {}", join(&args.input));
+    if let Some(path) = &args.input_file {
+        eprintln!("input file    {}", path);
+    }
+    if args.interactive {
+        eprintln!("typed inputs  {}", join(typed));
+    }
+    if let Some(max_steps) = args.max_steps {
+        eprintln!("max steps     {}", max_steps);
+    }
+    if let Some(timeout) = args.timeout {
+        eprintln!("timeout       {:?}", timeout);
+    }
+    if let Some(profile) = machine.profile() {
+        eprintln!("instructions  {}", profile.instructions);
+    }
+    match result {
+        Ok(status) => eprintln!("status        {:?}", status),
+        Err(err) => eprintln!("status        {}", err),
+    }
+}
+
+
+/// Run to the end, asking for input along the way if allowed to.
+fn run(args: &RunArgs, file: &ProgramFile, patches: &Patches, machine: &mut Machine) -> Result<Status, intcode::Error> {
+    machine.set_deadline(args.deadline());
+    if args.report {
+        machine.enable_profiling();
+    }
+    let format = args.output_format(file);
+    let mut typed = Vec::new();
+    loop {
+        let result = machine.run();
+        if result == Ok(Status::NeedsInput) && args.interactive {
+            print_output(format, machine);
+            if let Some(values) = args.read_input(file, machine) {
+                typed.extend(values);
+                continue
+            }
+        }
+        print_output(format, machine);
+        if args.report {
+            report(args, file, patches, machine, &typed, &result);
+        }
+        return result
+    }
+}
+
+
+fn search(file: &ProgramFile, target: Word, max: Word, max_steps: u64) -> i32 {
+    let mut template = file.machine();
+    template.precompile();
+
+    for noun in 0..=max {
+        for verb in 0..=max {
+            let mut machine = template.clone();
+            machine.poke(1, noun).unwrap();
+            machine.poke(2, verb).unwrap();
+            machine.set_fuel(Some(max_steps));
+            if machine.run() == Ok(Status::Halted) && machine.peek(0) == target {
+                println!("noun {} verb {}: {}", noun, verb, 100 * noun + verb);
+                return 0
+            }
+        }
+    }
+    eprintln!("no noun and verb up to {} give {}", max, target);
+    EXIT_NOT_FOUND
+}
+
+
+fn trace(args: &RunArgs, file: &ProgramFile) -> i32 {
+    let mut machine = args.machine(file, &args.patches(file));
+    let deadline = args.deadline();
+    loop {
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            return finish(Ok(Status::BudgetExhausted))
+        }
+
+        let pc = machine.pc();
+        let description = match Instruction::decode(machine.memory(), pc) {
+            Ok(instruction) => instruction.to_string(),
+            Err(_) => format!("{} (invalid)", machine.peek(pc)),
+        };
+        let base = machine.relative_base();
+
+        // neither of these ran the instruction, so there's nothing to print
+        let result = machine.step();
+        if result == Ok(Status::NeedsInput) {
+            if args.interactive && args.read_input(file, &mut machine).is_some() {
+                continue
+            }
+            return finish(result)
+        }
+        if result == Ok(Status::BudgetExhausted) {
+            return finish(result)
+        }
+
+        println!("{:>8}  {:<32} rb={}", pc, description, base);
+        for value in machine.drain_output() {
+            println!("{:>8}  -> {}", "", value);
+        }
+        if result != Ok(Status::Running) {
+            return finish(result)
+        }
+    }
+}
+
+
+fn bench(args: &RunArgs, file: &ProgramFile, iterations: u32) -> i32 {
+    let mut template = args.machine(file, &args.patches(file));
+    template.precompile();
+
+    // one profiled run to count instructions, and to check the run works at all
+    let mut machine = template.clone();
+    machine.enable_profiling();
+    let result = machine.run();
+    if result != Ok(Status::Halted) {
+        return finish(result)
+    }
+    let instructions = machine.profile().unwrap().instructions;
+
+    let mut fastest = Duration::MAX;
+    let start = Instant::now();
+    for _ in 0..iterations {
+        let mut machine = template.clone();
+        let run_start = Instant::now();
+        std::hint::black_box(machine.run()).ok();
+        fastest = fastest.min(run_start.elapsed());
+    }
+    let mean = start.elapsed() / iterations;
+
+    println!("instructions per run  {:>12}", instructions);
+    println!("mean                  {:>12?}", mean);
+    println!("fastest               {:>12?}", fastest);
+    println!("instructions / second {:>12.0}", instructions as f64 / mean.as_secs_f64());
+    0
+}
+
+
+fn main() {
+    /*
+    one binary for running, patching and looking inside Intcode programs
+    e.g. `cargo run --bin intcode -- run ../day5/input.txt --input 5`
+    or   `cargo run --bin intcode -- patch ../day2/input.txt --set 1=12,2=2`
+    */
+    let cli = Cli::parse();
+
+    let code = match cli.command {
+        Command::Run(args) => {
+            let file = load(&args.path);
+            let patches = args.patches(&file);
+            let mut machine = args.machine(&file, &patches);
+            finish(run(&args, &file, &patches, &mut machine))
+        }
+        Command::Patch { run: mut args, set, show } => {
+            args.patch.extend(set);
+            let file = load(&args.path);
+            let patches = args.patches(&file);
+            let mut machine = args.machine(&file, &patches);
+            let result = run(&args, &file, &patches, &mut machine);
+            if result == Ok(Status::Halted) {
+                println!("{}", machine.peek(show));
+            }
+            finish(result)
+        }
+        Command::Search { path, target, max, max_steps } => search(&load(&path), target, max, max_steps),
+        Command::Disasm { path } => {
+            let file = load(&path);
+            print!("{}", Cfg::build(&file.program).listing(&file.program));
+            0
+        }
+        Command::Trace(args) => trace(&args, &load(&args.path)),
+        Command::Bench { run: args, iterations } => bench(&args, &load(&args.path), iterations),
+    };
+    exit(code)
+}
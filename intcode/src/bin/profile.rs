@@ -0,0 +1,59 @@
+use std::env;
+
+use intcode::{load_program, Machine, Status, Word};
+
+
+fn main() {
+    /*
+    run the program at the given path with profiling on and print where the time went
+    any further numeric arguments are fed to the program as input, `--json` prints
+    the report as JSON instead of a table
+    e.g. `cargo run --release --bin profile ../day5/input.txt 5`
+    */
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 2 {
+        println!("Please input a path to the file containing the input data, followed by any inputs");
+        return
+    }
+
+    let program = match load_program(&args[1]) {
+        Ok(program) => program,
+        Err(err) => {
+            println!("failed to load input: {}", err);
+            return
+        }
+    };
+
+    let mut json = false;
+    let mut machine = Machine::new(program);
+    for arg in &args[2..] {
+        if arg == "--json" {
+            json = true;
+            continue
+        }
+        match arg.parse::<Word>() {
+            Ok(value) => machine.push_input(value),
+            Err(_) => {
+                println!("inputs must be numbers, not {}", arg);
+                return
+            }
+        }
+    }
+
+    machine.enable_profiling();
+    let result = machine.run();
+    let output = machine.drain_output();
+    let profile = machine.profile().unwrap();
+
+    if json {
+        println!("{}", profile.to_json(10));
+    } else {
+        println!("output: {:?}", output);
+        match result {
+            Ok(Status::Halted) => {}
+            Ok(status) => println!("stopped early: {:?}", status),
+            Err(err) => println!("stopped early: {}", err),
+        }
+        print!("\n{}", profile.table(10));
+    }
+}
@@ -0,0 +1,498 @@
+//! Static control-flow recovery for Intcode images.
+//!
+//! Starting from address 0 every reachable instruction is decoded by
+//! following fall-through and jumps whose target is an immediate. Jumps
+//! through memory can't be followed statically and are marked as indirect,
+//! with one exception: the calling convention used by the compiled puzzle
+//! inputs, where a caller stores its return address in a relative slot
+//! (`21101, 0, ret, 0`) before jumping to the callee, and the callee returns
+//! with a jump through that slot (`2105, 1, 0` or `2106, 0, 0`).
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use std::ops::Range;
+
+use crate::instruction::{Instruction, Opcode, Param};
+use crate::Word;
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exit {
+    Halt,
+    /// The last instruction is followed by the start of another block.
+    FallThrough(usize),
+    /// An unconditional jump to a known address.
+    Jump(usize),
+    Branch { taken: usize, fallthrough: usize },
+    /// A jump through memory that isn't a recognised return. `fallthrough`
+    /// is set when the jump is conditional.
+    Indirect { fallthrough: Option<usize> },
+    Call { target: usize, ret: usize },
+    Return,
+    /// The next word does not decode, or execution runs off the image.
+    Invalid,
+}
+
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    pub start: usize,
+    /// One past the last word of the last instruction.
+    pub end: usize,
+    pub instructions: Vec<(usize, Instruction)>,
+    pub exit: Exit,
+}
+
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Function {
+    pub entry: usize,
+    /// The `arb` the function opens with, if any.
+    pub frame_size: Option<Word>,
+    pub call_sites: Vec<usize>,
+}
+
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Cfg {
+    pub size: usize,
+    pub blocks: BTreeMap<usize, Block>,
+    pub functions: BTreeMap<usize, Function>,
+    /// Words never reached as code but read or written by reachable code.
+    pub data: Vec<Range<usize>>,
+    /// Words never reached as code and never referenced.
+    pub unreachable: Vec<Range<usize>>,
+    /// Words written through position mode parameters by reachable code,
+    /// with the addresses of the instructions writing them.
+    pub written: BTreeMap<usize, BTreeSet<usize>>,
+}
+
+
+/// Value stored by an instruction whose inputs are all immediates.
+fn constant_store(instruction: &Instruction) -> Option<(Param, Word)> {
+    let [a, b, c] = instruction.params;
+    let value = match (instruction.opcode, a, b) {
+        (Opcode::Add, Param::Immediate(a), Param::Immediate(b)) => a.wrapping_add(b),
+        (Opcode::Mul, Param::Immediate(a), Param::Immediate(b)) => a.wrapping_mul(b),
+        _ => return None,
+    };
+    Some((c, value))
+}
+
+
+/// What is statically known about a jump: whether it is taken (`None` if it
+/// depends on memory) and where it goes (`None` if through memory). Words
+/// that reachable code writes to are never trusted as constants.
+fn resolve_jump(instruction: &Instruction, address: usize, volatile: &BTreeSet<usize>) -> (Option<bool>, Option<usize>) {
+    let condition = match instruction.params[0] {
+        Param::Immediate(value) if !volatile.contains(&(address + 1)) => {
+            Some((value != 0) == (instruction.opcode == Opcode::JumpIfTrue))
+        }
+        _ => None,
+    };
+    let target = match instruction.params[1] {
+        Param::Immediate(target) if target >= 0 && !volatile.contains(&(address + 2)) => Some(target as usize),
+        _ => None,
+    };
+    (condition, target)
+}
+
+
+/// Addresses written through position mode parameters, and who writes them.
+fn written(instructions: &BTreeMap<usize, Instruction>) -> BTreeMap<usize, BTreeSet<usize>> {
+    let mut written = BTreeMap::<usize, BTreeSet<usize>>::new();
+    for (&writer, instruction) in instructions {
+        let param = match instruction.opcode {
+            Opcode::Add | Opcode::Mul | Opcode::LessThan | Opcode::Equals => instruction.params[2],
+            Opcode::Input => instruction.params[0],
+            _ => continue,
+        };
+        if let Param::Position(address) = param {
+            if address >= 0 {
+                written.entry(address as usize).or_default().insert(writer);
+            }
+        }
+    }
+    written
+}
+
+
+#[derive(Default)]
+struct Traversal {
+    instructions: BTreeMap<usize, Instruction>,
+    leaders: BTreeSet<usize>,
+    /// jump address -> (callee, return address)
+    calls: BTreeMap<usize, (usize, usize)>,
+    invalid: BTreeSet<usize>,
+}
+
+impl Traversal {
+    fn run(memory: &[Word], volatile: &BTreeSet<usize>) -> Traversal {
+        let mut traversal = Traversal::default();
+        let mut queue = vec![0];
+        traversal.leaders.insert(0);
+
+        while let Some(start) = queue.pop() {
+            let mut address = start;
+            // return address most recently stored in a relative slot
+            let mut pushed: Option<usize> = None;
+
+            while !traversal.instructions.contains_key(&address) {
+                let instruction = match Instruction::decode(memory, address) {
+                    Ok(instruction) if address < memory.len() => instruction,
+                    _ => {
+                        traversal.invalid.insert(address);
+                        break;
+                    }
+                };
+                traversal.instructions.insert(address, instruction);
+                let next = address + instruction.size();
+
+                if let Some((Param::Relative(_), value)) = constant_store(&instruction) {
+                    if value >= 0 && (value as usize) < memory.len() {
+                        pushed = Some(value as usize);
+                    }
+                }
+
+                match instruction.opcode {
+                    Opcode::Halt => break,
+                    Opcode::JumpIfTrue | Opcode::JumpIfFalse => {
+                        let (condition, target) = resolve_jump(&instruction, address, volatile);
+                        if condition == Some(false) {
+                            address = next;
+                            continue;
+                        }
+
+                        if let Some(target) = target {
+                            traversal.leaders.insert(target);
+                            queue.push(target);
+                            if let (Some(true), Some(ret)) = (condition, pushed) {
+                                traversal.calls.insert(address, (target, ret));
+                                traversal.leaders.insert(ret);
+                                queue.push(ret);
+                            }
+                        }
+
+                        if condition == Some(true) {
+                            break;
+                        }
+                        traversal.leaders.insert(next);
+                        address = next;
+                    }
+                    _ => address = next,
+                }
+            }
+        }
+
+        traversal
+    }
+}
+
+
+impl Cfg {
+    pub fn build(memory: &[Word]) -> Cfg {
+        // self-modifying code can turn constants into variables, so keep
+        // going until the set of written words stops growing
+        let mut volatile = BTreeSet::new();
+        let (traversal, written) = loop {
+            let traversal = Traversal::run(memory, &volatile);
+            let written = written(&traversal.instructions);
+            if written.keys().all(|word| volatile.contains(word)) {
+                break (traversal, written);
+            }
+            volatile.extend(written.keys());
+        };
+
+        let mut cfg = Cfg { size: memory.len(), ..Cfg::default() };
+        cfg.split_blocks(&traversal, &volatile);
+        cfg.written = written;
+        cfg.find_functions(&traversal.calls);
+        cfg.classify_regions(memory, &traversal.instructions);
+        cfg
+    }
+
+    fn split_blocks(&mut self, traversal: &Traversal, volatile: &BTreeSet<usize>) {
+        let instructions = &traversal.instructions;
+        let mut current: Option<Block> = None;
+
+        for (&address, &instruction) in instructions {
+            let continues = current.as_ref()
+                .is_some_and(|block| block.end == address && !traversal.leaders.contains(&address));
+            if !continues {
+                if let Some(mut block) = current.take() {
+                    block.exit = if instructions.contains_key(&block.end) {
+                        Exit::FallThrough(block.end)
+                    } else {
+                        Exit::Invalid
+                    };
+                    self.blocks.insert(block.start, block);
+                }
+                current = Some(Block { start: address, end: address, instructions: Vec::new(), exit: Exit::Invalid });
+            }
+
+            let block = current.as_mut().unwrap();
+            block.instructions.push((address, instruction));
+            block.end = address + instruction.size();
+
+            let exit = match instruction.opcode {
+                Opcode::Halt => Some(Exit::Halt),
+                Opcode::JumpIfTrue | Opcode::JumpIfFalse => {
+                    let fallthrough = block.end;
+                    match resolve_jump(&instruction, address, volatile) {
+                        (Some(false), _) => None,
+                        (Some(true), Some(target)) => Some(match traversal.calls.get(&address) {
+                            Some(&(target, ret)) => Exit::Call { target, ret },
+                            None => Exit::Jump(target),
+                        }),
+                        (Some(true), None) if matches!(instruction.params[1], Param::Relative(_)) => Some(Exit::Return),
+                        (Some(true), None) => Some(Exit::Indirect { fallthrough: None }),
+                        (None, Some(taken)) => Some(Exit::Branch { taken, fallthrough }),
+                        (None, None) => Some(Exit::Indirect { fallthrough: Some(fallthrough) }),
+                    }
+                }
+                _ => None,
+            };
+
+            if let Some(exit) = exit {
+                let mut block = current.take().unwrap();
+                block.exit = exit;
+                self.blocks.insert(block.start, block);
+            }
+        }
+
+        if let Some(mut block) = current {
+            block.exit = if traversal.invalid.contains(&block.end) { Exit::Invalid } else { Exit::FallThrough(block.end) };
+            self.blocks.insert(block.start, block);
+        }
+    }
+
+    fn find_functions(&mut self, calls: &BTreeMap<usize, (usize, usize)>) {
+        for (&site, &(target, _)) in calls {
+            let frame_size = self.blocks.get(&target)
+                .and_then(|block| block.instructions.first())
+                .and_then(|(_, instruction)| match (instruction.opcode, instruction.params[0]) {
+                    (Opcode::AdjustBase, Param::Immediate(size)) => Some(size),
+                    _ => None,
+                });
+            self.functions.entry(target)
+                .or_insert(Function { entry: target, frame_size, call_sites: Vec::new() })
+                .call_sites.push(site);
+        }
+    }
+
+    fn classify_regions(&mut self, memory: &[Word], instructions: &BTreeMap<usize, Instruction>) {
+        let mut code = vec![false; memory.len()];
+        let mut referenced = BTreeSet::new();
+        for (&address, instruction) in instructions {
+            for word in code.iter_mut().skip(address).take(instruction.size()) {
+                *word = true;
+            }
+            for param in instruction.params.iter().take(instruction.opcode.arity()) {
+                if let Param::Position(target) = param {
+                    referenced.insert(*target);
+                }
+            }
+        }
+
+        let mut address = 0;
+        while address < memory.len() {
+            if code[address] {
+                address += 1;
+                continue;
+            }
+            let start = address;
+            while address < memory.len() && !code[address] {
+                address += 1;
+            }
+            // runs of referenced words in a gap are data, the words between them unreachable
+            let mut from = start;
+            while from < address {
+                let is_data = referenced.contains(&(from as Word));
+                let mut to = from + 1;
+                while to < address && referenced.contains(&(to as Word)) == is_data {
+                    to += 1;
+                }
+                if is_data {
+                    self.data.push(from..to);
+                } else {
+                    self.unreachable.push(from..to);
+                }
+                from = to;
+            }
+        }
+    }
+
+    /// Whether some instruction other than `reader` writes `word`. An
+    /// instruction storing into its own parameters (as every instruction in
+    /// day2's programs does) reads them before the write lands.
+    pub fn is_patched(&self, word: usize, reader: usize) -> bool {
+        self.written.get(&word).is_some_and(|writers| writers.iter().any(|&writer| writer != reader))
+    }
+
+    pub fn successors(&self, block: &Block) -> Vec<usize> {
+        match block.exit {
+            Exit::Halt | Exit::Return | Exit::Invalid | Exit::Indirect { fallthrough: None } => vec![],
+            Exit::FallThrough(next) | Exit::Jump(next) => vec![next],
+            Exit::Indirect { fallthrough: Some(next) } => vec![next],
+            Exit::Branch { taken, fallthrough } => vec![taken, fallthrough],
+            Exit::Call { target, ret } => vec![target, ret],
+        }
+    }
+
+    /// A disassembly in address order: blocks as instructions under a label,
+    /// everything in between as raw words marked as data or unreachable.
+    pub fn listing(&self, memory: &[Word]) -> String {
+        let mut text = String::new();
+        let mut address = 0;
+
+        while address < self.size {
+            if let Some(block) = self.blocks.get(&address) {
+                if self.functions.contains_key(&address) {
+                    writeln!(text, "\nfn {}:", address).unwrap();
+                }
+                writeln!(text, "L{}:", address).unwrap();
+                for (address, instruction) in &block.instructions {
+                    writeln!(text, "{:>8}  {}", address, instruction).unwrap();
+                }
+                address = block.end;
+                continue;
+            }
+
+            // raw words, up to the next block and in rows of eight
+            let next = self.blocks.range(address..).next().map_or(self.size, |(&start, _)| start);
+            let kind = if self.data.iter().any(|range| range.contains(&address)) { "data" } else { "unreachable" };
+            let mut end = address + 1;
+            while end < next && end - address < 8
+                && self.data.iter().any(|range| range.contains(&end)) == (kind == "data") {
+                end += 1;
+            }
+            let words: Vec<String> = (address..end).map(|a| memory.get(a).copied().unwrap_or(0).to_string()).collect();
+            writeln!(text, "{:>8}  .{} {}", address, kind, words.join(", ")).unwrap();
+            address = end;
+        }
+        text
+    }
+
+    /// Graphviz rendering of the graph, one box per block.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph cfg {\n    node [shape=box, fontname=monospace];\n");
+
+        for block in self.blocks.values() {
+            let mut label = String::new();
+            if let Some(function) = self.functions.get(&block.start) {
+                write!(label, "function {}\\l", function.entry).unwrap();
+            }
+            for (address, instruction) in &block.instructions {
+                write!(label, "{}: {}\\l", address, instruction).unwrap();
+            }
+            let style = match block.exit {
+                Exit::Invalid | Exit::Indirect { .. } => ", color=red",
+                _ => "",
+            };
+            writeln!(dot, "    b{} [label=\"{}\"{}];", block.start, label, style).unwrap();
+        }
+
+        for block in self.blocks.values() {
+            let edges: Vec<(usize, &str)> = match block.exit {
+                Exit::FallThrough(next) => vec![(next, "")],
+                Exit::Jump(target) => vec![(target, "jump")],
+                Exit::Branch { taken, fallthrough } => vec![(taken, "taken"), (fallthrough, "not taken")],
+                Exit::Indirect { fallthrough: Some(next) } => vec![(next, "not taken")],
+                Exit::Call { target, ret } => vec![(target, "call"), (ret, "return")],
+                _ => vec![],
+            };
+            for (target, label) in edges {
+                if self.blocks.contains_key(&target) {
+                    writeln!(dot, "    b{} -> b{} [label=\"{}\"];", block.start, target, label).unwrap();
+                } else {
+                    writeln!(dot, "    b{} -> missing{} [label=\"{}\"];", block.start, target, label).unwrap();
+                }
+            }
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Machine;
+
+    // main stores its return address, calls a function at 12 that writes 3 + 4
+    // into the data word at 23, and prints it
+    const CALL: [Word; 26] = [
+        109, 100,
+        21101, 0, 9, 0,
+        1105, 1, 12,
+        4, 23,
+        99,
+        109, 2,
+        1101, 3, 4, 23,
+        109, -2,
+        2105, 1, 0,
+        0,
+        99, 99,
+    ];
+
+    #[test]
+    fn test_call() {
+        let mut machine = Machine::new(CALL.to_vec());
+        machine.run().unwrap();
+        assert_eq!(machine.drain_output(), vec![7]);
+
+        let cfg = Cfg::build(&CALL);
+        let exits: Vec<(usize, usize, Exit)> = cfg.blocks.values().map(|b| (b.start, b.end, b.exit)).collect();
+        assert_eq!(exits, vec![
+            (0, 9, Exit::Call { target: 12, ret: 9 }),
+            (9, 12, Exit::Halt),
+            (12, 23, Exit::Return),
+        ]);
+        assert_eq!(cfg.functions[&12], Function { entry: 12, frame_size: Some(2), call_sites: vec![6] });
+        assert_eq!(cfg.data, vec![23..24]);
+        assert_eq!(cfg.unreachable, vec![24..26]);
+
+        let dot = cfg.to_dot();
+        assert!(dot.contains("b0 -> b12 [label=\"call\"]"));
+        assert!(dot.contains("b0 -> b9 [label=\"return\"]"));
+        assert!(dot.contains("function 12"));
+
+        let listing = cfg.listing(&CALL);
+        assert!(listing.starts_with("L0:\n       0  arb 100\n"));
+        assert!(listing.contains("\nfn 12:\nL12:\n      12  arb 2\n"));
+        assert!(listing.ends_with("      23  .data 0\n      24  .unreachable 99, 99\n"));
+    }
+
+    #[test]
+    fn test_branches() {
+        // day5's jump example: 3,3,1105,-1,9,... where the condition is written by the input
+        let program = [3, 3, 1105, -1, 9, 1101, 0, 0, 12, 4, 12, 99, 1];
+        let cfg = Cfg::build(&program);
+        let exits: Vec<(usize, Exit)> = cfg.blocks.values().map(|b| (b.start, b.exit)).collect();
+        assert_eq!(exits, vec![
+            (0, Exit::Branch { taken: 9, fallthrough: 5 }),
+            (5, Exit::FallThrough(9)),
+            (9, Exit::Halt),
+        ]);
+        assert_eq!(cfg.data, vec![12..13]);
+
+        // jump through memory
+        let cfg = Cfg::build(&[6, 5, 4, 99, 3, 0]);
+        assert_eq!(cfg.blocks[&0].exit, Exit::Indirect { fallthrough: Some(3) });
+        assert_eq!(cfg.data, vec![4..6]);
+    }
+
+    #[test]
+    fn test_data_runs() {
+        // day5's opening: the input goes to 225 and is added into the
+        // instruction at 6, so decoding stops there. Only the two words it
+        // touches are data, not everything from 6 to 225.
+        let mut program = vec![3, 225, 1, 225, 6, 6, 1100, 1, 238, 225, 104, 0, 99];
+        program.resize(226, 0);
+        let cfg = Cfg::build(&program);
+        assert_eq!(cfg.data, vec![6..7, 225..226]);
+        assert_eq!(cfg.unreachable, vec![7..225]);
+    }
+}
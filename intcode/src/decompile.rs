@@ -0,0 +1,626 @@
+//! Lifting Intcode to structured pseudo-code.
+//!
+//! Works on the graph from `cfg`: each function (and `main` at address 0) is
+//! printed on its own, branches become `if`/`else` using immediate
+//! post-dominators as merge points, back-edges become `loop`/`while`, and
+//! whatever doesn't fit falls back to `goto`. Relative-mode operands are
+//! shown as `frame[n]`, offsets from the relative base when the function was
+//! entered, and reads of words no reachable code writes to are inlined as
+//! constants.
+//!
+//! Day5's diagnostic program patches its own opcodes from its input, so it
+//! reads best when decompiled from `traced_image`, which overlays the code
+//! as it looked when it actually ran.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use crate::cfg::{Block, Cfg, Exit};
+use crate::instruction::{Instruction, Opcode, Param};
+use crate::{Error, Machine, Status, Word};
+
+
+pub fn decompile(memory: &[Word]) -> String {
+    let cfg = Cfg::build(memory);
+    let decompiler = Decompiler { memory, cfg: &cfg };
+
+    let mut entries = vec![0];
+    entries.extend(cfg.functions.keys().filter(|&&entry| entry != 0));
+
+    let mut text = String::new();
+    for entry in entries {
+        if !text.is_empty() {
+            text.push('\n');
+        }
+        text.push_str(&decompiler.function(entry));
+    }
+    text
+}
+
+
+/// How many instructions `traced_image` runs before giving up on the trace.
+pub const TRACE_FUEL: u64 = 1_000_000;
+
+
+/// Run `program` on `inputs` and return the original image with every
+/// executed instruction replaced by the words it had when it first ran.
+/// Data is left as it was, so later writes don't hide the initial values.
+/// A program still running after `TRACE_FUEL` instructions gets its static
+/// image back unchanged.
+pub fn traced_image(program: &[Word], inputs: &[Word]) -> Result<Vec<Word>, Error> {
+    let mut image = program.to_vec();
+    let mut seen = BTreeSet::new();
+    let mut machine = Machine::new(program.to_vec());
+    machine.set_fuel(Some(TRACE_FUEL));
+    for &value in inputs {
+        machine.push_input(value);
+    }
+
+    loop {
+        let pc = machine.pc();
+        if seen.insert(pc) {
+            let size = Instruction::decode(machine.memory(), pc).map_or(1, |i| i.size());
+            for address in pc..pc + size {
+                if address >= image.len() {
+                    image.resize(address + 1, 0);
+                }
+                image[address] = machine.peek(address);
+            }
+        }
+        match machine.step()? {
+            Status::Running => continue,
+            Status::BudgetExhausted => return Ok(program.to_vec()),
+            _ => return Ok(image),
+        }
+    }
+}
+
+
+/// A comparison, kept apart so that it can be negated.
+struct Condition {
+    lhs: String,
+    op: &'static str,
+    rhs: String,
+}
+
+impl Condition {
+    fn negate(self) -> Condition {
+        let op = match self.op {
+            "<" => ">=",
+            ">=" => "<",
+            "==" => "!=",
+            _ => "==",
+        };
+        Condition { op, ..self }
+    }
+
+    fn render(&self) -> String {
+        format!("{} {} {}", self.lhs, self.op, self.rhs)
+    }
+}
+
+
+struct Decompiler<'a> {
+    memory: &'a [Word],
+    cfg: &'a Cfg,
+}
+
+impl<'a> Decompiler<'a> {
+    /// Render parameter `n` of the instruction at `at`. Where the parameter
+    /// was read from matters when the program rewrites its own parameters.
+    fn operand(&self, param: Param, at: usize, n: usize, frame: Option<Word>, inline: bool) -> String {
+        let word = at + n;
+        let patched = self.cfg.is_patched(word, at);
+        match param {
+            Param::Immediate(_) if patched => format!("mem[{}]", word),
+            Param::Immediate(value) => value.to_string(),
+            Param::Position(_) if patched => format!("mem[mem[{}]]", word),
+            Param::Position(address) => {
+                let constant = address >= 0
+                    && (address as usize) < self.memory.len()
+                    && !self.cfg.written.contains_key(&(address as usize));
+                if inline && constant {
+                    self.memory[address as usize].to_string()
+                } else {
+                    format!("mem[{}]", address)
+                }
+            }
+            Param::Relative(offset) => match frame.and_then(|base| base.checked_add(offset)) {
+                Some(slot) => format!("frame[{}]", slot),
+                None => format!("mem[rb + {}]", offset),
+            },
+        }
+    }
+
+    fn statement(&self, address: usize, instruction: &Instruction, frame: Option<Word>) -> Option<String> {
+        let [a, b, c] = instruction.params;
+        let value = |n: usize, param: Param| self.operand(param, address, n, frame, true);
+        let target = |n: usize, param: Param| self.operand(param, address, n, frame, false);
+        let constant = |n: usize, param: Param| match param {
+            Param::Immediate(value) if !self.cfg.is_patched(address + n, address) => Some(value),
+            _ => value(n, param).parse::<Word>().ok(),
+        };
+
+        let expression = match instruction.opcode {
+            Opcode::Add => match (constant(1, a), constant(2, b)) {
+                (Some(x), Some(y)) => x.wrapping_add(y).to_string(),
+                (Some(0), None) => value(2, b),
+                (None, Some(0)) => value(1, a),
+                (None, Some(y)) if y < 0 => format!("{} - {}", value(1, a), -y),
+                _ => format!("{} + {}", value(1, a), value(2, b)),
+            },
+            Opcode::Mul => match (constant(1, a), constant(2, b)) {
+                (Some(x), Some(y)) => x.wrapping_mul(y).to_string(),
+                (Some(1), None) => value(2, b),
+                (None, Some(1)) => value(1, a),
+                (None, Some(-1)) => format!("-{}", value(1, a)),
+                _ => format!("{} * {}", value(1, a), value(2, b)),
+            },
+            Opcode::LessThan => format!("{} < {}", value(1, a), value(2, b)),
+            Opcode::Equals => format!("{} == {}", value(1, a), value(2, b)),
+            Opcode::Input => return Some(format!("{} = input()", target(1, a))),
+            Opcode::Output => return Some(format!("output({})", value(1, a))),
+            Opcode::AdjustBase => match a {
+                // immediate adjustments are folded into the frame offsets
+                Param::Immediate(_) if frame.is_some() => return None,
+                _ => return Some(format!("rb += {}", value(1, a))),
+            },
+            Opcode::JumpIfTrue | Opcode::JumpIfFalse | Opcode::Halt => return None,
+        };
+
+        Some(format!("{} = {}", target(3, c), expression))
+    }
+
+    /// The condition under which the jump ending `block` is taken.
+    fn condition(&self, block: &Block, frame: Option<Word>) -> Condition {
+        let (address, jump) = *block.instructions.last().unwrap();
+        let tested = self.operand(jump.params[0], address, 1, frame, false);
+
+        // fold `t = a < b; jt t` into `a < b`
+        let mut condition = Condition { lhs: self.operand(jump.params[0], address, 1, frame, true), op: "!=", rhs: String::from("0") };
+        if let Some((previous, compare)) = block.instructions.iter().rev().nth(1) {
+            let op = match compare.opcode {
+                Opcode::LessThan => Some("<"),
+                Opcode::Equals => Some("=="),
+                _ => None,
+            };
+            if let Some(op) = op {
+                if self.operand(compare.params[2], *previous, 3, frame, false) == tested {
+                    condition = Condition {
+                        lhs: self.operand(compare.params[0], *previous, 1, frame, true),
+                        op,
+                        rhs: self.operand(compare.params[1], *previous, 2, frame, true),
+                    };
+                }
+            }
+        }
+
+        if jump.opcode == Opcode::JumpIfTrue {
+            condition
+        } else {
+            condition.negate()
+        }
+    }
+
+    fn function(&self, entry: usize) -> String {
+        let function = FunctionGraph::new(self.cfg, entry);
+        let mut emitter = Emitter {
+            decompiler: self,
+            graph: &function,
+            lines: Vec::new(),
+            emitted: BTreeSet::new(),
+            gotos: BTreeSet::new(),
+        };
+        emitter.region(entry, None, None, 1);
+
+        let mut text = match self.cfg.functions.get(&entry) {
+            _ if entry == 0 => String::from("fn main() {\n"),
+            Some(info) => match info.frame_size {
+                Some(size) => format!("fn f{}() {{ // frame size {}\n", entry, size),
+                None => format!("fn f{}() {{\n", entry),
+            },
+            None => format!("fn f{}() {{\n", entry),
+        };
+        for line in &emitter.lines {
+            match line {
+                Line::Label(address) if emitter.gotos.contains(address) => {
+                    text.push_str(&format!("L{}:\n", address));
+                }
+                Line::Label(_) => {}
+                Line::Text(indent, content) => {
+                    text.push_str(&"    ".repeat(*indent));
+                    text.push_str(content);
+                    text.push('\n');
+                }
+            }
+        }
+        text.push_str("}\n");
+        text
+    }
+}
+
+
+/// The blocks of one function, with calls treated as falling through to
+/// their return address.
+struct FunctionGraph<'a> {
+    cfg: &'a Cfg,
+    blocks: BTreeSet<usize>,
+    headers: BTreeSet<usize>,
+    ipdom: HashMap<usize, usize>,
+    /// Relative base at block entry, as an offset from the base on function entry.
+    frames: HashMap<usize, Option<Word>>,
+}
+
+impl<'a> FunctionGraph<'a> {
+    fn new(cfg: &'a Cfg, entry: usize) -> FunctionGraph<'a> {
+        let mut graph = FunctionGraph {
+            cfg,
+            blocks: BTreeSet::new(),
+            headers: BTreeSet::new(),
+            ipdom: HashMap::new(),
+            frames: HashMap::new(),
+        };
+
+        let mut stack = vec![(entry, Some(0))];
+        while let Some((address, frame)) = stack.pop() {
+            if !cfg.blocks.contains_key(&address) || !graph.blocks.insert(address) {
+                continue;
+            }
+            graph.frames.insert(address, frame);
+
+            let mut end_frame = frame;
+            for (_, instruction) in &cfg.blocks[&address].instructions {
+                if instruction.opcode == Opcode::AdjustBase {
+                    end_frame = match instruction.params[0] {
+                        Param::Immediate(value) => end_frame.and_then(|base| base.checked_add(value)),
+                        _ => None,
+                    };
+                }
+            }
+            for next in graph.successors(address) {
+                if next <= address {
+                    graph.headers.insert(next);
+                }
+                stack.push((next, end_frame));
+            }
+        }
+
+        graph.post_dominators();
+        graph
+    }
+
+    fn successors(&self, address: usize) -> Vec<usize> {
+        let block = &self.cfg.blocks[&address];
+        let successors = match block.exit {
+            Exit::Call { ret, .. } => vec![ret],
+            _ => self.cfg.successors(block),
+        };
+        successors.into_iter().filter(|next| self.cfg.blocks.contains_key(next)).collect()
+    }
+
+    fn post_dominators(&mut self) {
+        const EXIT: usize = usize::MAX;
+        let nodes: Vec<usize> = self.blocks.iter().copied().collect();
+        let all: BTreeSet<usize> = nodes.iter().copied().chain(Some(EXIT)).collect();
+
+        let mut pdom: BTreeMap<usize, BTreeSet<usize>> = nodes.iter().map(|&n| (n, all.clone())).collect();
+        pdom.insert(EXIT, Some(EXIT).into_iter().collect());
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &node in nodes.iter().rev() {
+                let mut successors = self.successors(node);
+                if successors.is_empty() {
+                    successors.push(EXIT);
+                }
+                let mut set = successors.iter()
+                    .map(|s| pdom[s].clone())
+                    .reduce(|a, b| a.intersection(&b).copied().collect())
+                    .unwrap();
+                set.insert(node);
+                if set != pdom[&node] {
+                    pdom.insert(node, set);
+                    changed = true;
+                }
+            }
+        }
+
+        for &node in &nodes {
+            // blocks that never reach the exit (infinite loops) have no merge point
+            if !pdom[&node].contains(&EXIT) || pdom[&node].len() == all.len() {
+                continue;
+            }
+            let strict = pdom[&node].len() - 1;
+            let closest = pdom[&node].iter()
+                .find(|&&p| p != node && p != EXIT && pdom[&p].len() == strict);
+            if let Some(&closest) = closest {
+                self.ipdom.insert(node, closest);
+            }
+        }
+    }
+}
+
+
+enum Line {
+    Label(usize),
+    Text(usize, String),
+}
+
+
+#[derive(Clone, Copy)]
+struct Loop {
+    header: usize,
+    exit: Option<usize>,
+}
+
+
+struct Emitter<'a, 'b> {
+    decompiler: &'b Decompiler<'a>,
+    graph: &'b FunctionGraph<'a>,
+    lines: Vec<Line>,
+    emitted: BTreeSet<usize>,
+    gotos: BTreeSet<usize>,
+}
+
+impl<'a, 'b> Emitter<'a, 'b> {
+    fn line(&mut self, indent: usize, text: String) {
+        self.lines.push(Line::Text(indent, text));
+    }
+
+    /// Emit blocks starting at `start` until reaching `stop`.
+    fn region(&mut self, start: usize, stop: Option<usize>, current_loop: Option<Loop>, indent: usize) {
+        let mut next = Some(start);
+
+        while let Some(address) = next {
+            if Some(address) == stop {
+                return;
+            }
+            if let Some(current) = current_loop {
+                if address == current.header {
+                    self.line(indent, String::from("continue"));
+                    return;
+                }
+                if Some(address) == current.exit {
+                    self.line(indent, String::from("break"));
+                    return;
+                }
+            }
+            if !self.graph.blocks.contains(&address) {
+                // past the end of the image, or otherwise not decodable
+                self.line(indent, format!("goto {}", address));
+                return;
+            }
+            if self.emitted.contains(&address) {
+                self.gotos.insert(address);
+                self.line(indent, format!("goto L{}", address));
+                return;
+            }
+
+            if self.graph.headers.contains(&address) {
+                let exit = self.graph.ipdom.get(&address).copied();
+                self.emit_loop(address, exit, indent);
+                next = exit;
+            } else {
+                next = self.block(address, current_loop, indent);
+            }
+        }
+    }
+
+    fn emit_loop(&mut self, header: usize, exit: Option<usize>, indent: usize) {
+        let this_loop = Some(Loop { header, exit });
+        let graph = self.graph;
+        let block = &graph.cfg.blocks[&header];
+        let frame = self.graph.frames[&header];
+
+        // a header that only tests and leaves is a `while`
+        if let (Exit::Branch { taken, fallthrough }, 1) = (block.exit, block.instructions.len()) {
+            if Some(taken) == exit || Some(fallthrough) == exit {
+                self.emitted.insert(header);
+                self.lines.push(Line::Label(header));
+                let mut condition = self.decompiler.condition(block, frame);
+                let body = if Some(taken) == exit {
+                    condition = condition.negate();
+                    fallthrough
+                } else {
+                    taken
+                };
+                self.line(indent, format!("while {} {{", condition.render()));
+                self.region(body, None, this_loop, indent + 1);
+                self.close_loop(indent);
+                return;
+            }
+        }
+
+        self.line(indent, String::from("loop {"));
+        if let Some(next) = self.block(header, this_loop, indent + 1) {
+            self.region(next, None, this_loop, indent + 1);
+        }
+        self.close_loop(indent);
+    }
+
+    fn close_loop(&mut self, indent: usize) {
+        if let Some(Line::Text(depth, text)) = self.lines.last() {
+            if *depth == indent + 1 && text == "continue" {
+                self.lines.pop();
+            }
+        }
+        self.line(indent, String::from("}"));
+    }
+
+    /// Emit one block and its terminator, returning where execution continues.
+    fn block(&mut self, address: usize, current_loop: Option<Loop>, indent: usize) -> Option<usize> {
+        self.emitted.insert(address);
+        self.lines.push(Line::Label(address));
+
+        let graph = self.graph;
+        let block = &graph.cfg.blocks[&address];
+        let mut frame = self.graph.frames[&address];
+        for (at, instruction) in &block.instructions {
+            // the return address pushed before a call is implied by the call itself
+            let pushes_return = match (block.exit, instruction.opcode, instruction.params) {
+                (Exit::Call { ret, .. }, Opcode::Add, [Param::Immediate(x), Param::Immediate(y), Param::Relative(_)]) => {
+                    x.wrapping_add(y) == ret as Word
+                }
+                _ => false,
+            };
+            if pushes_return {
+                continue;
+            }
+            if let Some(statement) = self.decompiler.statement(*at, instruction, frame) {
+                self.line(indent, statement);
+            }
+            if instruction.opcode == Opcode::AdjustBase {
+                frame = match instruction.params[0] {
+                    Param::Immediate(value) => frame.and_then(|base| base.checked_add(value)),
+                    _ => None,
+                };
+            }
+        }
+
+        let (last, jump) = *block.instructions.last().unwrap();
+        let jump_target = self.decompiler.operand(jump.params[1], last, 2, frame, false);
+
+        match block.exit {
+            Exit::FallThrough(next) | Exit::Jump(next) => Some(next),
+            Exit::Call { target, ret } => {
+                self.line(indent, format!("f{}()", target));
+                Some(ret)
+            }
+            Exit::Halt => {
+                self.line(indent, String::from("halt"));
+                None
+            }
+            Exit::Return => {
+                self.line(indent, String::from("return"));
+                None
+            }
+            Exit::Indirect { fallthrough: None } => {
+                self.line(indent, format!("goto *{}", jump_target));
+                None
+            }
+            Exit::Indirect { fallthrough: Some(next) } => {
+                let condition = self.decompiler.condition(block, frame);
+                self.line(indent, format!("if {} {{", condition.render()));
+                self.line(indent + 1, format!("goto *{}", jump_target));
+                self.line(indent, String::from("}"));
+                Some(next)
+            }
+            Exit::Invalid => {
+                self.line(indent, format!("// continues into {}, which can't be decoded ahead of time", block.end));
+                None
+            }
+            Exit::Branch { taken, fallthrough } => {
+                let merge = self.graph.ipdom.get(&address).copied();
+                let condition = self.decompiler.condition(block, frame);
+
+                if Some(taken) == merge {
+                    self.line(indent, format!("if {} {{", condition.negate().render()));
+                    self.region(fallthrough, merge, current_loop, indent + 1);
+                } else if Some(fallthrough) == merge {
+                    self.line(indent, format!("if {} {{", condition.render()));
+                    self.region(taken, merge, current_loop, indent + 1);
+                } else {
+                    self.line(indent, format!("if {} {{", condition.render()));
+                    self.region(taken, merge, current_loop, indent + 1);
+                    self.line(indent, String::from("} else {"));
+                    self.region(fallthrough, merge, current_loop, indent + 1);
+                }
+                self.line(indent, String::from("}"));
+                merge
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_while() {
+        // print a countdown from the input
+        let program = [3, 20, 1006, 20, 14, 4, 20, 1001, 20, -1, 20, 1105, 1, 2, 99, 0, 0, 0, 0, 0, 0];
+        assert_eq!(decompile(&program), "\
+fn main() {
+    mem[20] = input()
+    while mem[20] != 0 {
+        output(mem[20])
+        mem[20] = mem[20] - 1
+    }
+    halt
+}
+");
+    }
+
+    #[test]
+    fn test_if_with_patched_condition() {
+        // day5's jump test: the input is written over the condition of the jump
+        let program = [3, 3, 1105, -1, 9, 1101, 0, 0, 12, 4, 12, 99, 1];
+        assert_eq!(decompile(&program), "\
+fn main() {
+    mem[3] = input()
+    if mem[3] == 0 {
+        mem[12] = 0
+    }
+    output(mem[12])
+    halt
+}
+");
+    }
+
+    #[test]
+    fn test_traced_image() {
+        // day5 style: the input completes the opcode at address 6
+        let program = [3, 9, 1, 9, 6, 6, 0, 4, 99, 0];
+        assert_eq!(decompile(&program), "\
+fn main() {
+    mem[9] = input()
+    mem[6] = mem[9] + mem[6]
+    // continues into 6, which can't be decoded ahead of time
+}
+");
+        let image = traced_image(&program, &[104]).unwrap();
+        assert_eq!(&image[..6], &program[..6]);
+        assert_eq!(image[6], 104);
+        assert_eq!(decompile(&image), "\
+fn main() {
+    mem[9] = input()
+    mem[6] = mem[9] + mem[6]
+    output(4)
+    halt
+}
+");
+
+        // patches in a jump to itself, so the trace never finishes
+        let program = [1101, 1100, 5, 4, 0, 1, 4];
+        assert_eq!(traced_image(&program, &[]).unwrap(), program);
+    }
+
+    #[test]
+    fn test_frame_overflow() {
+        // frame offsets past the end of a word are left as plain relative reads
+        let text = decompile(&[109, Word::MAX, 109, 1, 204, 1, 99]);
+        assert!(text.contains("output(mem[rb + 1])"), "{}", text);
+    }
+
+    #[test]
+    fn test_function_call() {
+        let program = [
+            109, 100, 21101, 0, 9, 0, 1105, 1, 12, 4, 23, 99,
+            109, 2, 1101, 3, 4, 23, 109, -2, 2105, 1, 0, 0,
+        ];
+        assert_eq!(decompile(&program), "\
+fn main() {
+    f12()
+    output(mem[23])
+    halt
+}
+
+fn f12() { // frame size 2
+    mem[23] = 7
+    return
+}
+");
+    }
+}
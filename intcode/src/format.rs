@@ -0,0 +1,335 @@
+//! Program files.
+//!
+//! The text format is the puzzle input format with some slack: words may be
+//! separated by commas, whitespace or both, a trailing comma is fine and `#`
+//! starts a comment that runs to the end of the line. Comments of the form
+//! `# key: value` before the first word make up the header, where the key is
+//! a single word and one of `name`, `io` or `memory`:
+//!
+//! ```text
+//! # name: day5 diagnostic
+//! # io: numeric
+//! # memory: 4096
+//! 3,225,1,225,6,6,1100,1,238,225,   # the self test starts here
+//! 104,0, ...
+//! ```
+//!
+//! The binary format is `MAGIC` followed by the header and then each word as a
+//! zigzag LEB128 varint, which keeps the small numbers most programs are made
+//! of to a byte or two. `decode` tells the two apart by the magic number.
+
+use std::fmt;
+use std::fs;
+
+use crate::{Machine, Word, MAX_MEMORY};
+
+/// The start of every binary program file.
+pub const MAGIC: &[u8] = b"\x7fIC\x01";
+
+
+/// How a program expects its input and output to be treated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IoMode {
+    Numeric,
+    Ascii,
+}
+
+impl fmt::Display for IoMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IoMode::Numeric => write!(f, "numeric"),
+            IoMode::Ascii => write!(f, "ascii"),
+        }
+    }
+}
+
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Header {
+    pub name: Option<String>,
+    pub io: Option<IoMode>,
+    /// Memory to allocate up front, if more than the program itself.
+    pub memory: Option<usize>,
+}
+
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProgramFile {
+    pub header: Header,
+    pub program: Vec<Word>,
+}
+
+impl ProgramFile {
+    pub fn new(program: Vec<Word>) -> ProgramFile {
+        ProgramFile { header: Header::default(), program }
+    }
+
+    /// The words of memory a machine starts with: the program, or more if
+    /// the header asks for it.
+    pub fn memory_size(&self) -> usize {
+        self.header.memory.unwrap_or(0).max(self.program.len())
+    }
+
+    /// A machine loaded with the program, with memory sized per the header.
+    pub fn machine(&self) -> Machine {
+        let mut program = self.program.clone();
+        program.resize(self.memory_size(), 0);
+        Machine::new(program)
+    }
+}
+
+
+fn parse_header(header: &mut Header, key: &str, value: &str) -> Result<(), String> {
+    match key {
+        "name" => header.name = Some(value.to_string()),
+        "io" => header.io = Some(match value {
+            "numeric" => IoMode::Numeric,
+            "ascii" => IoMode::Ascii,
+            _ => return Err(format!("unknown io mode {:?}, expected numeric or ascii", value)),
+        }),
+        "memory" => {
+            let memory = value.parse::<usize>().map_err(|err| format!("bad memory size {:?}: {}", value, err))?;
+            if memory > MAX_MEMORY {
+                return Err(format!("memory size {} is over the limit of {}", memory, MAX_MEMORY));
+            }
+            header.memory = Some(memory);
+        }
+        _ => return Err(format!("unknown header key {:?}, expected name, io or memory", key)),
+    }
+    Ok(())
+}
+
+
+/// Whether a comment's text before the colon reads as a header key rather
+/// than prose, like `# note to self: ...`.
+fn is_key(text: &str) -> bool {
+    !text.is_empty() && text.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+
+pub fn parse_text(text: &str) -> Result<ProgramFile, String> {
+    let mut header = Header::default();
+    let mut program = Vec::new();
+
+    for (number, line) in text.lines().enumerate() {
+        let (code, comment) = match line.find('#') {
+            Some(start) => (&line[..start], Some(&line[start + 1..])),
+            None => (line, None),
+        };
+
+        if let (true, Some(comment)) = (program.is_empty() && code.trim().is_empty(), comment) {
+            if let Some((key, value)) = comment.split_once(':').filter(|(key, _)| is_key(key.trim())) {
+                parse_header(&mut header, key.trim(), value.trim())
+                    .map_err(|err| format!("line {}: {}", number + 1, err))?;
+            }
+        }
+
+        for word in code.split(|c: char| c == ',' || c.is_whitespace()).filter(|word| !word.is_empty()) {
+            program.push(
+                word.parse::<Word>().map_err(|err| format!("line {}: {:?} {}", number + 1, word, err))?
+            );
+        }
+    }
+
+    if program.is_empty() {
+        return Err(String::from("no program found"));
+    }
+    Ok(ProgramFile { header, program })
+}
+
+
+pub fn write_text(file: &ProgramFile) -> String {
+    let mut text = String::new();
+    if let Some(name) = &file.header.name {
+        text.push_str(&format!("# name: {}\n", name));
+    }
+    if let Some(io) = file.header.io {
+        text.push_str(&format!("# io: {}\n", io));
+    }
+    if let Some(memory) = file.header.memory {
+        text.push_str(&format!("# memory: {}\n", memory));
+    }
+
+    let words: Vec<String> = file.program.iter().map(|word| word.to_string()).collect();
+    text.push_str(&words.join(","));
+    text.push('\n');
+    text
+}
+
+
+fn zigzag(word: Word) -> u64 {
+    ((word << 1) ^ (word >> 63)) as u64
+}
+
+
+fn unzigzag(value: u64) -> Word {
+    ((value >> 1) as Word) ^ -((value & 1) as Word)
+}
+
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+
+fn read_varint(bytes: &[u8], offset: &mut usize) -> Result<u64, String> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = *bytes.get(*offset).ok_or_else(|| format!("truncated at byte {}", offset))?;
+        *offset += 1;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(format!("varint too long at byte {}", offset))
+}
+
+
+/// The binary encoding. An absent name or io mode is written as zero, and the
+/// memory size as one more than itself so that zero can mean absent.
+pub fn write_binary(file: &ProgramFile) -> Vec<u8> {
+    let mut bytes = MAGIC.to_vec();
+
+    let name = file.header.name.as_deref().unwrap_or("");
+    write_varint(&mut bytes, name.len() as u64);
+    bytes.extend_from_slice(name.as_bytes());
+    bytes.push(match file.header.io {
+        None => 0,
+        Some(IoMode::Numeric) => 1,
+        Some(IoMode::Ascii) => 2,
+    });
+    write_varint(&mut bytes, file.header.memory.map_or(0, |memory| memory as u64 + 1));
+
+    write_varint(&mut bytes, file.program.len() as u64);
+    for &word in &file.program {
+        write_varint(&mut bytes, zigzag(word));
+    }
+    bytes
+}
+
+
+pub fn parse_binary(bytes: &[u8]) -> Result<ProgramFile, String> {
+    if !bytes.starts_with(MAGIC) {
+        return Err(String::from("not a binary Intcode file"));
+    }
+    let mut offset = MAGIC.len();
+    let mut header = Header::default();
+
+    let length = read_varint(bytes, &mut offset)? as usize;
+    let name = bytes.get(offset..offset.saturating_add(length)).ok_or("truncated name")?;
+    if length > 0 {
+        header.name = Some(String::from_utf8(name.to_vec()).map_err(|err| err.to_string())?);
+    }
+    offset += length;
+
+    header.io = match bytes.get(offset) {
+        Some(0) => None,
+        Some(1) => Some(IoMode::Numeric),
+        Some(2) => Some(IoMode::Ascii),
+        Some(other) => return Err(format!("unknown io mode {}", other)),
+        None => return Err(String::from("truncated header")),
+    };
+    offset += 1;
+
+    header.memory = match read_varint(bytes, &mut offset)? {
+        0 => None,
+        memory if memory - 1 > MAX_MEMORY as u64 => {
+            return Err(format!("memory size {} is over the limit of {}", memory - 1, MAX_MEMORY));
+        }
+        memory => Some(memory as usize - 1),
+    };
+
+    let count = read_varint(bytes, &mut offset)? as usize;
+    // every word takes at least a byte, so don't trust a count larger than that
+    let mut program = Vec::with_capacity(count.min(bytes.len() - offset));
+    for _ in 0..count {
+        program.push(unzigzag(read_varint(bytes, &mut offset)?));
+    }
+    if offset != bytes.len() {
+        return Err(format!("{} bytes of trailing data", bytes.len() - offset));
+    }
+    Ok(ProgramFile { header, program })
+}
+
+
+/// Read either format, telling them apart by the magic number.
+pub fn decode(bytes: &[u8]) -> Result<ProgramFile, String> {
+    if bytes.starts_with(MAGIC) {
+        return parse_binary(bytes);
+    }
+    let text = std::str::from_utf8(bytes).map_err(|_| "neither a text nor a binary Intcode file")?;
+    parse_text(text)
+}
+
+
+pub fn load(path: &str) -> Result<ProgramFile, String> {
+    let bytes = fs::read(path).map_err(|err| err.to_string())?;
+    decode(&bytes)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text() {
+        let text = "# name: echo\n# io: ascii\n# a note: not a header\n\n3,0,  4 0\n\t99, # done\n";
+        let file = parse_text(text).unwrap();
+        assert_eq!(file.program, vec![3, 0, 4, 0, 99]);
+        assert_eq!(file.header, Header { name: Some(String::from("echo")), io: Some(IoMode::Ascii), memory: None });
+
+        // the puzzle inputs are still fine
+        assert_eq!(parse_text("1,0,0,3,99\n").unwrap(), ProgramFile::new(vec![1, 0, 0, 3, 99]));
+        // header-looking comments after the program has started are just comments
+        assert_eq!(parse_text("99\n# memory: 10\n").unwrap().header.memory, None);
+
+        assert_eq!(parse_text("1,2\n3,x,4").unwrap_err(), "line 2: \"x\" invalid digit found in string");
+        assert_eq!(parse_text("# io: binary\n99").unwrap_err(), "line 1: unknown io mode \"binary\", expected numeric or ascii");
+        assert_eq!(parse_text("# just a comment\n").unwrap_err(), "no program found");
+        assert_eq!(parse_text("# author: me\n99").unwrap_err(), "line 1: unknown header key \"author\", expected name, io or memory");
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let file = ProgramFile {
+            header: Header { name: Some(String::from("extremes")), io: Some(IoMode::Numeric), memory: Some(100) },
+            program: vec![0, 1, -1, 63, -64, 64, Word::MAX, Word::MIN, 19690720],
+        };
+        assert_eq!(parse_text(&write_text(&file)).unwrap(), file);
+        assert_eq!(decode(write_text(&file).as_bytes()).unwrap(), file);
+
+        let bytes = write_binary(&file);
+        assert_eq!(decode(&bytes).unwrap(), file);
+        assert_eq!(parse_binary(&bytes[..bytes.len() - 1]).unwrap_err(), format!("truncated at byte {}", bytes.len() - 1));
+
+        // no memory size and a size of zero are different things
+        for memory in [None, Some(0), Some(MAX_MEMORY)] {
+            let file = ProgramFile { header: Header { memory, ..Header::default() }, program: vec![99] };
+            assert_eq!(parse_binary(&write_binary(&file)).unwrap(), file);
+            assert_eq!(parse_text(&write_text(&file)).unwrap(), file);
+        }
+
+        let bare = ProgramFile::new(vec![1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50]);
+        let bytes = write_binary(&bare);
+        // magic, three empty header fields, the count and a byte per word but 99
+        assert_eq!(bytes.len(), 4 + 3 + 1 + 11 + 2);
+        assert_eq!(decode(&bytes).unwrap(), bare);
+        assert_eq!(bare.machine().memory().len(), 12);
+        assert_eq!(file.machine().memory().len(), 100);
+        assert_eq!((bare.memory_size(), file.memory_size()), (12, 100));
+    }
+
+    #[test]
+    fn test_zigzag() {
+        for word in [0, 1, -1, 2, -2, 1 << 40, Word::MAX, Word::MIN] {
+            assert_eq!(unzigzag(zigzag(word)), word);
+        }
+        assert_eq!((zigzag(0), zigzag(-1), zigzag(1), zigzag(-2)), (0, 1, 2, 3));
+    }
+}
@@ -0,0 +1,176 @@
+//! An undo log for stepping a machine backwards.
+//!
+//! With history switched on every instruction the machine completes leaves a
+//! `Step` behind: where it ran, the relative base before it, the old value of
+//! any word it overwrote and any input it used up or output it produced. That
+//! is enough for `Machine::step_back` to put everything back the way it was,
+//! and for `History::last_writer` to say which instruction is to blame for
+//! the value in a word, which is what you want to know about day2's program.
+//!
+//! Profiles and self-modification tracking are not rewound.
+
+use crate::Word;
+
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Step {
+    pub pc: usize,
+    pub relative_base: Word,
+    /// Addresses written and the value they held before.
+    pub writes: Vec<(usize, Word)>,
+    /// Size of memory before, so that growth can be undone.
+    pub memory_size: usize,
+    pub input: Option<Word>,
+    pub output: Option<Word>,
+    /// Whether the step used up fuel, which halting doesn't.
+    pub fueled: bool,
+}
+
+
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    steps: Vec<Step>,
+    /// The instruction currently executing, committed once it completes.
+    pending: Option<Step>,
+}
+
+impl History {
+    pub fn new() -> History {
+        History::default()
+    }
+
+    /// The number of instructions recorded.
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    pub fn steps(&self) -> &[Step] {
+        &self.steps
+    }
+
+    /// The instruction count and address of the last instruction to write
+    /// `address`, if any recorded instruction did.
+    pub fn last_writer(&self, address: usize) -> Option<(usize, usize)> {
+        self.steps.iter().enumerate().rev()
+            .find(|(_, step)| step.writes.iter().any(|&(written, _)| written == address))
+            .map(|(count, step)| (count, step.pc))
+    }
+
+    pub(crate) fn begin(&mut self, pc: usize, relative_base: Word, memory_size: usize) {
+        self.pending = Some(Step { pc, relative_base, writes: Vec::new(), memory_size, input: None, output: None, fueled: false });
+    }
+
+    pub(crate) fn on_write(&mut self, address: usize, old: Word) {
+        if let Some(step) = self.pending.as_mut() {
+            step.writes.push((address, old));
+        }
+    }
+
+    pub(crate) fn on_input(&mut self, value: Word) {
+        if let Some(step) = self.pending.as_mut() {
+            step.input = Some(value);
+        }
+    }
+
+    pub(crate) fn on_output(&mut self, value: Word) {
+        if let Some(step) = self.pending.as_mut() {
+            step.output = Some(value);
+        }
+    }
+
+    pub(crate) fn commit(&mut self, fueled: bool) {
+        if let Some(mut step) = self.pending.take() {
+            step.fueled = fueled;
+            self.steps.push(step);
+        }
+    }
+
+    pub(crate) fn pop(&mut self) -> Option<Step> {
+        self.steps.pop()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::{Machine, Status};
+
+    #[test]
+    fn test_step_back() {
+        // the day2 example: 1,9,10,3,2,3,11,0,99,30,40,50 becomes 3500,9,10,70,...
+        let program = vec![1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50];
+        let mut machine = Machine::new(program.clone());
+        machine.record_history();
+        assert_eq!(machine.run(), Ok(Status::Halted));
+        assert_eq!(machine.peek(0), 3500);
+
+        let history = machine.history().unwrap();
+        assert_eq!(history.len(), 3);
+        assert_eq!(history.last_writer(3), Some((0, 0)));
+        assert_eq!(history.last_writer(0), Some((1, 4)));
+        assert_eq!(history.last_writer(9), None);
+
+        assert!(machine.step_back());
+        assert!(!machine.is_halted());
+        assert_eq!((machine.pc(), machine.peek(0)), (8, 3500));
+        assert!(machine.step_back());
+        assert_eq!((machine.pc(), machine.peek(0)), (4, 1));
+
+        assert!(machine.rewind_to(0));
+        assert_eq!(machine.memory(), &program[..]);
+        assert!(!machine.step_back());
+        assert!(!machine.rewind_to(1));
+
+        // and forwards again
+        assert_eq!(machine.run(), Ok(Status::Halted));
+        assert_eq!(machine.peek(0), 3500);
+    }
+
+    #[test]
+    fn test_io_and_growth() {
+        let mut machine = Machine::new(vec![3, 0, 109, 10, 21101, 2, 3, 5, 4, 15, 99]);
+        machine.record_history();
+        machine.push_input(7);
+        machine.run().unwrap();
+        assert_eq!(machine.memory().len(), 16);
+        assert_eq!(machine.history().unwrap().last_writer(15), Some((2, 4)));
+
+        assert!(machine.rewind_to(1));
+        assert_eq!(machine.memory().len(), 11);
+        assert_eq!((machine.pc(), machine.relative_base()), (2, 0));
+        assert_eq!(machine.drain_output(), Vec::new());
+
+        assert!(machine.rewind_to(0));
+        assert_eq!(machine.peek(0), 3);
+        assert_eq!(machine.run(), Ok(Status::Halted));
+        assert_eq!(machine.drain_output(), vec![5]);
+        assert_eq!(machine.peek(0), 7);
+    }
+
+    #[test]
+    fn test_fuel() {
+        let program = vec![1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50];
+        let mut machine = Machine::new(program.clone());
+        machine.record_history();
+        machine.set_fuel(Some(10));
+        assert_eq!(machine.run(), Ok(Status::Halted));
+        // halting is free, so only the add and the mul cost anything
+        assert_eq!(machine.fuel(), Some(8));
+        assert!(machine.step_back());
+        assert_eq!(machine.fuel(), Some(8));
+        assert!(machine.rewind_to(0));
+        assert_eq!(machine.fuel(), Some(10));
+
+        // steps run without a budget give nothing back to one set later
+        let mut machine = Machine::new(program);
+        machine.record_history();
+        machine.run().unwrap();
+        machine.set_fuel(Some(5));
+        assert!(machine.rewind_to(0));
+        assert_eq!(machine.fuel(), Some(5));
+    }
+}
@@ -0,0 +1,209 @@
+//! Decoded instructions.
+//!
+//! Decoding pulls the opcode and parameter modes out of the instruction word
+//! once (the `% 100` / `/ 1000` arithmetic from day5's `get_2_params`) so that
+//! the machine can cache the result and skip it on every later execution.
+
+use std::fmt;
+
+use crate::{Error, Word};
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Opcode {
+    Add,
+    Mul,
+    Input,
+    Output,
+    JumpIfTrue,
+    JumpIfFalse,
+    LessThan,
+    Equals,
+    AdjustBase,
+    Halt,
+}
+
+impl Opcode {
+    pub fn from_word(value: Word) -> Option<Opcode> {
+        match value {
+            1 => Some(Opcode::Add),
+            2 => Some(Opcode::Mul),
+            3 => Some(Opcode::Input),
+            4 => Some(Opcode::Output),
+            5 => Some(Opcode::JumpIfTrue),
+            6 => Some(Opcode::JumpIfFalse),
+            7 => Some(Opcode::LessThan),
+            8 => Some(Opcode::Equals),
+            9 => Some(Opcode::AdjustBase),
+            99 => Some(Opcode::Halt),
+            _ => None,
+        }
+    }
+
+    pub fn mnemonic(self) -> &'static str {
+        match self {
+            Opcode::Add => "add",
+            Opcode::Mul => "mul",
+            Opcode::Input => "in",
+            Opcode::Output => "out",
+            Opcode::JumpIfTrue => "jt",
+            Opcode::JumpIfFalse => "jf",
+            Opcode::LessThan => "lt",
+            Opcode::Equals => "eq",
+            Opcode::AdjustBase => "arb",
+            Opcode::Halt => "halt",
+        }
+    }
+
+    /// Number of parameters following the instruction word.
+    pub fn arity(self) -> usize {
+        match self {
+            Opcode::Add | Opcode::Mul | Opcode::LessThan | Opcode::Equals => 3,
+            Opcode::JumpIfTrue | Opcode::JumpIfFalse => 2,
+            Opcode::Input | Opcode::Output | Opcode::AdjustBase => 1,
+            Opcode::Halt => 0,
+        }
+    }
+}
+
+
+/// A parameter with its mode resolved. The payload is the raw word that
+/// followed the instruction; addresses are only checked when used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Param {
+    Position(Word),
+    Immediate(Word),
+    Relative(Word),
+}
+
+impl fmt::Display for Param {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Param::Position(address) => write!(f, "[{}]", address),
+            Param::Immediate(value) => write!(f, "{}", value),
+            Param::Relative(offset) if *offset < 0 => write!(f, "[rb-{}]", -offset),
+            Param::Relative(offset) => write!(f, "[rb+{}]", offset),
+        }
+    }
+}
+
+
+/// How the word following an instruction is to be used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Mode {
+    Position,
+    Immediate,
+    Relative,
+}
+
+impl Mode {
+    fn param(self, value: Word) -> Param {
+        match self {
+            Mode::Position => Param::Position(value),
+            Mode::Immediate => Param::Immediate(value),
+            Mode::Relative => Param::Relative(value),
+        }
+    }
+}
+
+
+/// What the instruction word says on its own: the opcode and the parameter
+/// modes. This is the part of decoding that needs the arithmetic, so it is
+/// what the machine caches; the parameters are read from memory as they are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Header {
+    /// The instruction word this was decoded from.
+    pub word: Word,
+    pub opcode: Opcode,
+    /// Only the first `opcode.arity()` entries are meaningful.
+    pub modes: [Mode; 3],
+}
+
+impl Header {
+    /// Decode the instruction word `word`, found at `address`.
+    pub fn decode(word: Word, address: usize) -> Result<Header, Error> {
+        let opcode = Opcode::from_word(word % 100)
+            .ok_or(Error::UnknownOpcode { pc: address, opcode: word % 100 })?;
+
+        let mut modes = [Mode::Immediate; 3];
+        let mut divisor = 100;
+        for mode in modes.iter_mut().take(opcode.arity()) {
+            *mode = match word / divisor % 10 {
+                0 => Mode::Position,
+                1 => Mode::Immediate,
+                2 => Mode::Relative,
+                mode => return Err(Error::UnknownMode { pc: address, mode }),
+            };
+            divisor *= 10;
+        }
+
+        Ok(Header { word, opcode, modes })
+    }
+
+    /// The whole instruction, taking the parameters from the words after
+    /// `address`.
+    pub fn instruction(&self, memory: &[Word], address: usize) -> Instruction {
+        let mut params = [Param::Immediate(0); 3];
+        for (i, param) in params.iter_mut().enumerate().take(self.opcode.arity()) {
+            *param = self.modes[i].param(memory.get(address + 1 + i).copied().unwrap_or(0));
+        }
+        Instruction { opcode: self.opcode, params }
+    }
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Instruction {
+    pub opcode: Opcode,
+    /// Only the first `opcode.arity()` entries are meaningful.
+    pub params: [Param; 3],
+}
+
+impl Instruction {
+    /// Decode the instruction starting at `address`. Memory past the end of
+    /// `memory` reads as zero, the same as it does for the machine.
+    pub fn decode(memory: &[Word], address: usize) -> Result<Instruction, Error> {
+        let word = memory.get(address).copied().unwrap_or(0);
+        Ok(Header::decode(word, address)?.instruction(memory, address))
+    }
+
+    /// Number of words the instruction occupies, including the instruction word.
+    pub fn size(&self) -> usize {
+        self.opcode.arity() + 1
+    }
+}
+
+/// Assembly style text, e.g. `mul [4], 3, [4]`.
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.opcode.mnemonic())?;
+        for (i, param) in self.params.iter().take(self.opcode.arity()).enumerate() {
+            write!(f, "{}{}", if i == 0 { " " } else { ", " }, param)?;
+        }
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode() {
+        let instruction = Instruction::decode(&[1002, 4, 3, 4, 33], 0).unwrap();
+        assert_eq!(instruction.opcode, Opcode::Mul);
+        assert_eq!(&instruction.params, &[Param::Position(4), Param::Immediate(3), Param::Position(4)]);
+        assert_eq!(instruction.size(), 4);
+        assert_eq!(instruction.to_string(), "mul [4], 3, [4]");
+
+        let instruction = Instruction::decode(&[204, -1], 0).unwrap();
+        assert_eq!(instruction.opcode, Opcode::Output);
+        assert_eq!(instruction.params[0], Param::Relative(-1));
+        assert_eq!(instruction.to_string(), "out [rb-1]");
+
+        assert_eq!(Instruction::decode(&[99], 0).unwrap().size(), 1);
+        assert_eq!(Instruction::decode(&[301, 0, 0, 0], 0), Err(Error::UnknownMode { pc: 0, mode: 3 }));
+        assert_eq!(Instruction::decode(&[0, 13], 1), Err(Error::UnknownOpcode { pc: 1, opcode: 13 }));
+    }
+}
@@ -0,0 +1,637 @@
+//! A reusable Intcode machine.
+//!
+//! The `compute` functions in day2 and day5 run a program start to finish in one
+//! call. The later puzzles need a machine that keeps its state between calls so
+//! that it can pause when it runs out of input and be resumed once more input is
+//! available, which is what `Machine` provides.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Instant;
+
+pub mod adventure;
+pub mod cfg;
+pub mod decompile;
+pub mod format;
+pub mod history;
+pub mod instruction;
+pub mod patch;
+pub mod profile;
+pub mod reference;
+pub mod selfmod;
+
+use history::History;
+use instruction::{Header, Instruction, Opcode, Param};
+use profile::Profile;
+use selfmod::SelfModification;
+
+/// The value stored in a single memory cell.
+pub type Word = i64;
+
+/// Programs may write past the end of their image, but not arbitrarily far.
+pub const MAX_MEMORY: usize = 1 << 24;
+
+/// How many instructions `run` executes between looks at the clock.
+pub const DEADLINE_INTERVAL: u64 = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    /// The last instruction completed and the machine can keep going.
+    Running,
+    /// The machine is parked on an input instruction with an empty input queue.
+    NeedsInput,
+    /// The machine has executed opcode 99.
+    Halted,
+    /// The step budget or the deadline ran out. Nothing is lost: top up the
+    /// budget or move the deadline and call `run` again to carry on.
+    BudgetExhausted,
+}
+
+impl Status {
+    /// The status as a string, e.g. `needs-input`. The bindings return
+    /// these, and the conformance fixtures are written in them.
+    pub fn name(self) -> &'static str {
+        match self {
+            Status::Running => "running",
+            Status::NeedsInput => "needs-input",
+            Status::Halted => "halted",
+            Status::BudgetExhausted => "budget-exhausted",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    UnknownOpcode { pc: usize, opcode: Word },
+    UnknownMode { pc: usize, mode: Word },
+    InvalidAddress { pc: usize, address: Word },
+    ImmediateWrite { pc: usize },
+    /// Only raised with strict self-modification tracking.
+    SelfModifying { pc: usize, address: usize },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::UnknownOpcode { pc, opcode } => write!(f, "unknown opcode {} at {}", opcode, pc),
+            Error::UnknownMode { pc, mode } => write!(f, "unknown parameter mode {} at {}", mode, pc),
+            Error::InvalidAddress { pc, address } => write!(f, "invalid address {} at {}", address, pc),
+            Error::ImmediateWrite { pc } => write!(f, "write to an immediate mode parameter at {}", pc),
+            Error::SelfModifying { pc, address } => write!(f, "self-modifying code at {} (address {})", pc, address),
+        }
+    }
+}
+
+impl Error {
+    /// The name of the variant, for comparing errors without their details.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::UnknownOpcode { .. } => "UnknownOpcode",
+            Error::UnknownMode { .. } => "UnknownMode",
+            Error::InvalidAddress { .. } => "InvalidAddress",
+            Error::ImmediateWrite { .. } => "ImmediateWrite",
+            Error::SelfModifying { .. } => "SelfModifying",
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+
+/// Parse a program in the text format, ignoring any header. See `format`.
+pub fn parse_program(text: &str) -> Result<Vec<Word>, String> {
+    format::parse_text(text).map(|file| file.program)
+}
+
+
+/// Load a program in either format, ignoring any header. See `format`.
+pub fn load_program(path: &str) -> Result<Vec<Word>, String> {
+    format::load(path).map(|file| file.program)
+}
+
+
+/// The header of every word that decodes as an instruction word.
+fn decode_image(memory: &[Word]) -> Arc<[Option<Header>]> {
+    memory.iter().enumerate().map(|(address, &word)| Header::decode(word, address).ok()).collect()
+}
+
+
+#[derive(Debug, Clone)]
+pub struct Machine {
+    memory: Vec<Word>,
+    pc: usize,
+    relative_base: Word,
+    input: VecDeque<Word>,
+    output: VecDeque<Word>,
+    halted: bool,
+    /// The decoded instruction word at every address of the image, shared
+    /// between clones. An entry is only used while memory still holds the
+    /// word it was decoded from, so writes need no bookkeeping.
+    cache: Arc<[Option<Header>]>,
+    caching: bool,
+    self_modification: Option<SelfModification>,
+    profile: Option<Profile>,
+    /// Instructions left to execute, if limited.
+    fuel: Option<u64>,
+    deadline: Option<Instant>,
+    history: Option<History>,
+}
+
+impl Machine {
+    pub fn new(program: Vec<Word>) -> Machine {
+        Machine {
+            cache: decode_image(&program),
+            caching: true,
+            self_modification: None,
+            profile: None,
+            fuel: None,
+            deadline: None,
+            history: None,
+            memory: program,
+            pc: 0,
+            relative_base: 0,
+            input: VecDeque::new(),
+            output: VecDeque::new(),
+            halted: false,
+        }
+    }
+
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    pub fn relative_base(&self) -> Word {
+        self.relative_base
+    }
+
+    pub fn memory(&self) -> &[Word] {
+        &self.memory
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    /// Read a cell, treating everything past the end of memory as zero.
+    pub fn peek(&self, address: usize) -> Word {
+        self.memory.get(address).copied().unwrap_or(0)
+    }
+
+    /// Write a cell, growing memory if needed. Addresses from `MAX_MEMORY`
+    /// on are refused, the same as when the program writes there.
+    pub fn poke(&mut self, address: usize, value: Word) -> Result<(), Error> {
+        if address >= MAX_MEMORY {
+            return Err(Error::InvalidAddress { pc: self.pc, address: address.min(Word::MAX as usize) as Word });
+        }
+        if address >= self.memory.len() {
+            self.memory.resize(address + 1, 0);
+        }
+        self.memory[address] = value;
+        Ok(())
+    }
+
+    /// Turn the instruction cache on or off. With it off every instruction is
+    /// decoded each time it runs, which is how day2 and day5 behave.
+    pub fn set_caching(&mut self, caching: bool) {
+        self.caching = caching;
+        self.cache = if caching { decode_image(&self.memory) } else { Vec::new().into() };
+    }
+
+    /// Decode memory again as it is now. The image is decoded when the
+    /// machine is made, so this is only worth it once instruction words have
+    /// been patched or memory has grown; clones made afterwards share the
+    /// new cache.
+    pub fn precompile(&mut self) {
+        if self.caching {
+            self.cache = decode_image(&self.memory);
+        }
+    }
+
+    /// Start recording writes into the instruction stream. In strict mode
+    /// such writes stop the machine with `Error::SelfModifying` instead.
+    pub fn track_self_modification(&mut self, strict: bool) {
+        self.self_modification = Some(SelfModification::new(strict));
+    }
+
+    pub fn self_modification(&self) -> Option<&SelfModification> {
+        self.self_modification.as_ref()
+    }
+
+    /// Start counting instructions, memory accesses and I/O from here on.
+    pub fn enable_profiling(&mut self) {
+        self.profile = Some(Profile::default());
+    }
+
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_ref()
+    }
+
+    /// Start recording an undo log, so that the machine can be stepped
+    /// backwards to any point from here on.
+    pub fn record_history(&mut self) {
+        self.history = Some(History::new());
+    }
+
+    pub fn history(&self) -> Option<&History> {
+        self.history.as_ref()
+    }
+
+    /// Undo the last recorded instruction. Returns false if there is none.
+    pub fn step_back(&mut self) -> bool {
+        let step = match self.history.as_mut().and_then(|history| history.pop()) {
+            Some(step) => step,
+            None => return false,
+        };
+
+        // memory only shrinks back afterwards, so every address is still there
+        for &(address, old) in step.writes.iter().rev() {
+            self.memory[address] = old;
+        }
+        self.memory.truncate(step.memory_size);
+        // the input went from the front of the queue, the output onto the
+        // back, unless it has been taken since
+        if let Some(value) = step.input {
+            self.input.push_front(value);
+        }
+        if step.output.is_some() {
+            self.output.pop_back();
+        }
+        if let Some(fuel) = self.fuel.as_mut().filter(|_| step.fueled) {
+            *fuel += 1;
+        }
+        self.pc = step.pc;
+        self.relative_base = step.relative_base;
+        self.halted = false;
+        true
+    }
+
+    /// Step backwards until `count` recorded instructions remain. Returns
+    /// false, without changing anything, if fewer than that were recorded.
+    pub fn rewind_to(&mut self, count: usize) -> bool {
+        match &self.history {
+            Some(history) if count <= history.len() => {
+                while self.history.as_ref().is_some_and(|history| history.len() > count) {
+                    self.step_back();
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Limit the machine to `fuel` more instructions, or lift the limit with
+    /// `None`. Each executed instruction uses up one unit.
+    pub fn set_fuel(&mut self, fuel: Option<u64>) {
+        self.fuel = fuel;
+    }
+
+    pub fn fuel(&self) -> Option<u64> {
+        self.fuel
+    }
+
+    /// Make `run` give up once `deadline` has passed. The clock is only looked
+    /// at every `DEADLINE_INTERVAL` instructions, so it may overrun slightly.
+    pub fn set_deadline(&mut self, deadline: Option<Instant>) {
+        self.deadline = deadline;
+    }
+
+    pub fn push_input(&mut self, value: Word) {
+        self.input.push_back(value);
+    }
+
+    /// Queue a line of ASCII input, including the terminating newline.
+    pub fn push_line(&mut self, line: &str) {
+        for byte in line.bytes() {
+            self.input.push_back(Word::from(byte));
+        }
+        self.input.push_back(Word::from(b'\n'));
+    }
+
+    pub fn pop_output(&mut self) -> Option<Word> {
+        self.output.pop_front()
+    }
+
+    pub fn drain_output(&mut self) -> Vec<Word> {
+        self.output.drain(..).collect()
+    }
+
+    /// Drain the output queue as text. Values outside of ASCII are written as
+    /// numbers so that nothing is silently dropped.
+    pub fn take_ascii(&mut self) -> String {
+        let mut text = String::new();
+        for value in self.output.drain(..) {
+            if (0..128).contains(&value) {
+                text.push(value as u8 as char);
+            } else {
+                text.push_str(&value.to_string());
+            }
+        }
+        text
+    }
+
+    /// Run until the program halts, needs input that has not been queued or
+    /// runs out of budget.
+    pub fn run(&mut self) -> Result<Status, Error> {
+        let mut steps: u64 = 0;
+        loop {
+            if steps.is_multiple_of(DEADLINE_INTERVAL) && self.deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return Ok(Status::BudgetExhausted);
+            }
+            steps += 1;
+
+            match self.step()? {
+                Status::Running => continue,
+                status => return Ok(status),
+            }
+        }
+    }
+
+    /// Execute a single instruction, unless the fuel has run out.
+    // inlined into `run` along with the helpers below, without which a step
+    // costs about twice as much and the cache makes little difference
+    #[inline(always)]
+    pub fn step(&mut self) -> Result<Status, Error> {
+        if self.halted {
+            return Ok(Status::Halted);
+        }
+        if self.fuel == Some(0) {
+            return Ok(Status::BudgetExhausted);
+        }
+
+        let pc = self.pc;
+        if let Some(history) = self.history.as_mut() {
+            history.begin(pc, self.relative_base, self.memory.len());
+        }
+        let instruction = self.fetch()?;
+        if let Some(tracker) = self.self_modification.as_mut() {
+            if let Some(event) = tracker.on_execute(pc, instruction.size()) {
+                return Err(Error::SelfModifying { pc, address: event.address });
+            }
+        }
+        let [a, b, c] = instruction.params;
+        let mut next = pc + instruction.size();
+
+        match instruction.opcode {
+            Opcode::Add => {
+                let value = self.read(a)?.wrapping_add(self.read(b)?);
+                self.write(c, value)?;
+            }
+            Opcode::Mul => {
+                let value = self.read(a)?.wrapping_mul(self.read(b)?);
+                self.write(c, value)?;
+            }
+            Opcode::Input => match self.input.pop_front() {
+                Some(value) => {
+                    self.write(a, value)?;
+                    if let Some(history) = self.history.as_mut() {
+                        history.on_input(value);
+                    }
+                }
+                None => return Ok(Status::NeedsInput),
+            },
+            Opcode::Output => {
+                let value = self.read(a)?;
+                self.output.push_back(value);
+                if let Some(history) = self.history.as_mut() {
+                    history.on_output(value);
+                }
+            }
+            Opcode::JumpIfTrue => {
+                if self.read(a)? != 0 {
+                    let target = self.read(b)?;
+                    next = self.address(target)?;
+                }
+            }
+            Opcode::JumpIfFalse => {
+                if self.read(a)? == 0 {
+                    let target = self.read(b)?;
+                    next = self.address(target)?;
+                }
+            }
+            Opcode::LessThan => {
+                let value = (self.read(a)? < self.read(b)?) as Word;
+                self.write(c, value)?;
+            }
+            Opcode::Equals => {
+                let value = (self.read(a)? == self.read(b)?) as Word;
+                self.write(c, value)?;
+            }
+            Opcode::AdjustBase => {
+                self.relative_base = self.relative_base.wrapping_add(self.read(a)?);
+            }
+            Opcode::Halt => {
+                if let Some(profile) = self.profile.as_mut() {
+                    profile.on_instruction(pc, instruction.opcode);
+                }
+                if let Some(history) = self.history.as_mut() {
+                    history.commit(false);
+                }
+                self.halted = true;
+                return Ok(Status::Halted);
+            }
+        }
+
+        if let Some(profile) = self.profile.as_mut() {
+            profile.on_instruction(pc, instruction.opcode);
+        }
+        if let Some(fuel) = self.fuel.as_mut() {
+            *fuel -= 1;
+        }
+        if let Some(history) = self.history.as_mut() {
+            history.commit(self.fuel.is_some());
+        }
+        self.pc = next;
+        Ok(Status::Running)
+    }
+
+    /// Decode the instruction at the program counter, going through the cache.
+    #[inline(always)]
+    fn fetch(&self) -> Result<Instruction, Error> {
+        let pc = self.pc;
+        let word = self.peek(pc);
+        let header = match self.cache.get(pc) {
+            // anything else was never an instruction or has been rewritten
+            Some(Some(header)) if header.word == word => *header,
+            _ => Header::decode(word, pc)?,
+        };
+        Ok(header.instruction(&self.memory, pc))
+    }
+
+    #[inline(always)]
+    fn address(&self, value: Word) -> Result<usize, Error> {
+        if value < 0 || value as usize >= MAX_MEMORY {
+            return Err(Error::InvalidAddress { pc: self.pc, address: value });
+        }
+        Ok(value as usize)
+    }
+
+    #[inline(always)]
+    fn read(&mut self, param: Param) -> Result<Word, Error> {
+        let address = match param {
+            Param::Position(address) => self.address(address)?,
+            Param::Immediate(value) => return Ok(value),
+            Param::Relative(offset) => self.address(self.relative_base.wrapping_add(offset))?,
+        };
+        if let Some(profile) = self.profile.as_mut() {
+            profile.on_read(address);
+        }
+        Ok(self.peek(address))
+    }
+
+    #[inline(always)]
+    fn write(&mut self, param: Param, value: Word) -> Result<(), Error> {
+        let address = match param {
+            Param::Position(address) => self.address(address)?,
+            Param::Immediate(_) => return Err(Error::ImmediateWrite { pc: self.pc }),
+            Param::Relative(offset) => self.address(self.relative_base.wrapping_add(offset))?,
+        };
+        if let Some(tracker) = self.self_modification.as_mut() {
+            if let Some(event) = tracker.on_write(self.pc, address) {
+                return Err(Error::SelfModifying { pc: self.pc, address: event.address });
+            }
+        }
+        if let Some(profile) = self.profile.as_mut() {
+            profile.on_write(address);
+        }
+        if let Some(history) = self.history.as_mut() {
+            history.on_write(address, self.memory.get(address).copied().unwrap_or(0));
+        }
+        self.poke(address, value)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_with(program: &[Word], inputs: &[Word]) -> Vec<Word> {
+        let mut machine = Machine::new(program.to_vec());
+        for &value in inputs {
+            machine.push_input(value);
+        }
+        assert_eq!(machine.run(), Ok(Status::Halted));
+        machine.drain_output()
+    }
+
+    #[test]
+    fn test_compute() {
+        // 1,1,1,4,99,5,6,0,99 becomes 30,1,1,4,2,5,6,0,99.
+        let mut machine = Machine::new(vec![1, 1, 1, 4, 99, 5, 6, 0, 99]);
+        machine.run().unwrap();
+        assert_eq!(machine.memory(), &[30, 1, 1, 4, 2, 5, 6, 0, 99]);
+    }
+
+    #[test]
+    fn test_jmp() {
+        let program = [3, 3, 1105, -1, 9, 1101, 0, 0, 12, 4, 12, 99, 1];
+        assert_eq!(run_with(&program, &[0]), vec![0]);
+        assert_eq!(run_with(&program, &[10]), vec![1]);
+    }
+
+    #[test]
+    fn test_relative_mode() {
+        // the day9 quine
+        let quine = vec![109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99];
+        assert_eq!(run_with(&quine, &[]), quine);
+
+        assert_eq!(run_with(&[104, 1125899906842624, 99], &[]), vec![1125899906842624]);
+    }
+
+    #[test]
+    fn test_pause_for_input() {
+        let mut machine = Machine::new(vec![3, 0, 4, 0, 99]);
+        assert_eq!(machine.run(), Ok(Status::NeedsInput));
+        machine.push_input(7);
+        assert_eq!(machine.run(), Ok(Status::Halted));
+        assert_eq!(machine.drain_output(), vec![7]);
+    }
+
+    #[test]
+    fn test_cache_invalidation() {
+        // outputs 5, rewrites its own output instruction to print 6, and jumps back
+        let program = vec![104, 5, 1008, 1, 6, 17, 1005, 17, 16, 1101, 0, 6, 1, 1105, 1, 0, 99, 0];
+        assert_eq!(run_with(&program, &[]), vec![5, 6]);
+
+        let mut machine = Machine::new(program.clone());
+        machine.precompile();
+        machine.run().unwrap();
+        assert_eq!(machine.drain_output(), vec![5, 6]);
+
+        let mut machine = Machine::new(program);
+        machine.set_caching(false);
+        machine.run().unwrap();
+        assert_eq!(machine.drain_output(), vec![5, 6]);
+    }
+
+    #[test]
+    fn test_shared_cache() {
+        let template = Machine::new(vec![1101, 2, 3, 9, 4, 9, 99, 0, 0, 0]);
+        assert!(template.cache[0].is_some());
+        assert!(template.cache[7].is_none());
+
+        // clones keep reading the one cache, from any thread
+        let mut clone = template.clone();
+        let clone = std::thread::spawn(move || {
+            clone.run().unwrap();
+            clone
+        });
+        let mut clone = clone.join().unwrap();
+        assert_eq!(clone.drain_output(), vec![5]);
+        assert!(Arc::ptr_eq(&clone.cache, &template.cache));
+
+        // and a rewritten instruction word is decoded afresh, without
+        // touching the shared entry
+        let mut clone = template.clone();
+        clone.poke(0, 1102).unwrap();
+        clone.run().unwrap();
+        assert_eq!(clone.drain_output(), vec![6]);
+        assert_eq!(template.cache[0].map(|header| header.opcode), Some(Opcode::Add));
+    }
+
+    #[test]
+    fn test_budget() {
+        // counts up in address 7 forever
+        let mut machine = Machine::new(vec![1001, 7, 1, 7, 1105, 1, 0, 0]);
+        machine.set_fuel(Some(9));
+        assert_eq!(machine.run(), Ok(Status::BudgetExhausted));
+        assert_eq!((machine.pc(), machine.peek(7), machine.fuel()), (4, 5, Some(0)));
+        assert_eq!(machine.step(), Ok(Status::BudgetExhausted));
+
+        // topping up carries on from where it stopped
+        machine.set_fuel(Some(2));
+        assert_eq!(machine.run(), Ok(Status::BudgetExhausted));
+        assert_eq!((machine.pc(), machine.peek(7)), (4, 6));
+
+        machine.set_fuel(None);
+        machine.set_deadline(Some(Instant::now()));
+        assert_eq!(machine.run(), Ok(Status::BudgetExhausted));
+        machine.set_deadline(Some(Instant::now() + std::time::Duration::from_millis(20)));
+        assert_eq!(machine.run(), Ok(Status::BudgetExhausted));
+        assert!(machine.peek(7) > 6);
+
+        // a program that halts in time is unaffected, and waiting for input is free
+        let mut machine = Machine::new(vec![3, 0, 4, 0, 99]);
+        machine.set_fuel(Some(3));
+        assert_eq!(machine.run(), Ok(Status::NeedsInput));
+        assert_eq!(machine.fuel(), Some(3));
+        machine.push_input(7);
+        assert_eq!(machine.run(), Ok(Status::Halted));
+        assert_eq!(machine.drain_output(), vec![7]);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(Machine::new(vec![42]).run(), Err(Error::UnknownOpcode { pc: 0, opcode: 42 }));
+        assert_eq!(Machine::new(vec![1, -1, 0, 0]).run(), Err(Error::InvalidAddress { pc: 0, address: -1 }));
+        assert_eq!(Machine::new(vec![11101, 1, 1, 0]).run(), Err(Error::ImmediateWrite { pc: 0 }));
+
+        let mut machine = Machine::new(vec![99]);
+        let address = MAX_MEMORY as Word;
+        assert_eq!(machine.poke(MAX_MEMORY, 1), Err(Error::InvalidAddress { pc: 0, address }));
+        assert_eq!(machine.poke(usize::MAX, 1), Err(Error::InvalidAddress { pc: 0, address: Word::MAX }));
+        assert_eq!(machine.poke(MAX_MEMORY - 1, 1), Ok(()));
+        assert_eq!(machine.memory().len(), MAX_MEMORY);
+    }
+}
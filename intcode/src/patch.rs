@@ -0,0 +1,215 @@
+//! Patch specifications.
+//!
+//! Day2 runs its program with the noun written to address 1 and the verb to
+//! address 2. A patch spec says the same thing as `1=12,2=2`. Each item is a
+//! target and a value:
+//!
+//! - `5=7` writes one word
+//! - `10..20=0` fills addresses 10 to 19, `10..=20=0` includes 20
+//! - `noun=12` or `table+3=-1` goes through a symbol file, as written by an
+//!   assembler: one `name address` (or `name = address`) per line, `#` for
+//!   comments. Values may be symbols too.
+//!
+//! Every target is checked against the memory the machine starts with (the
+//! program, or more if its header asks for it), so a typo can't quietly grow
+//! memory instead. `Patches` displays in the same syntax, which
+//! is how run reports record what was patched.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
+
+use crate::{Error, Machine, Word};
+
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Symbols {
+    addresses: HashMap<String, usize>,
+}
+
+impl Symbols {
+    pub fn new() -> Symbols {
+        Symbols::default()
+    }
+
+    pub fn parse(text: &str) -> Result<Symbols, String> {
+        let mut symbols = Symbols::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let fields: Vec<&str> = line.split(|c: char| c == '=' || c.is_whitespace())
+                .filter(|field| !field.is_empty())
+                .collect();
+            let (name, address) = match fields[..] {
+                [name, address] if is_name(name) => (name, address),
+                _ => return Err(format!("line {}: expected a name and an address, not {:?}", number + 1, line)),
+            };
+            let address = address.parse::<usize>()
+                .map_err(|_| format!("line {}: bad address {:?}", number + 1, address))?;
+            symbols.insert(name, address);
+        }
+        Ok(symbols)
+    }
+
+    pub fn insert(&mut self, name: &str, address: usize) {
+        self.addresses.insert(name.to_string(), address);
+    }
+
+    pub fn get(&self, name: &str) -> Option<usize> {
+        self.addresses.get(name).copied()
+    }
+}
+
+
+fn is_name(text: &str) -> bool {
+    let mut chars = text.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+
+/// Resolve a number, `name`, `name+offset` or `name-offset`.
+fn resolve(text: &str, symbols: &Symbols) -> Result<Word, String> {
+    let text = text.trim();
+    if let Ok(value) = text.parse::<Word>() {
+        return Ok(value);
+    }
+
+    let split = text.find(['+', '-']).unwrap_or(text.len());
+    let (name, offset) = text.split_at(split);
+    let name = name.trim();
+    if !is_name(name) {
+        return Err(format!("{:?} is neither a number nor a symbol", text));
+    }
+    let base = symbols.get(name).ok_or_else(|| format!("unknown symbol {:?}", name))? as Word;
+    let offset = match offset.trim() {
+        "" => 0,
+        offset => offset.replace(' ', "").parse::<Word>().map_err(|_| format!("bad offset in {:?}", text))?,
+    };
+    base.checked_add(offset).ok_or_else(|| format!("{:?} is out of range", text))
+}
+
+
+/// Writes of `value` to every address in `range`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Patch {
+    pub range: Range<usize>,
+    pub value: Word,
+}
+
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Patches(pub Vec<Patch>);
+
+impl Patches {
+    /// Parse a spec for a machine that starts with `size` words of memory.
+    pub fn parse(spec: &str, symbols: &Symbols, size: usize) -> Result<Patches, String> {
+        let mut patches = Vec::new();
+        for item in spec.split(',').map(str::trim).filter(|item| !item.is_empty()) {
+            let (target, value) = item.rsplit_once('=')
+                .filter(|(target, _)| !target.is_empty())
+                .ok_or_else(|| format!("expected TARGET=VALUE, not {:?}", item))?;
+            let address = |text: &str| -> Result<usize, String> {
+                match resolve(text, symbols)? {
+                    address if address < 0 => Err(format!("{:?} is a negative address", text)),
+                    address => Ok(address as usize),
+                }
+            };
+
+            let range = if let Some((start, end)) = target.split_once("..=") {
+                address(start)?..address(end)? + 1
+            } else if let Some((start, end)) = target.split_once("..") {
+                address(start)?..address(end)?
+            } else {
+                let start = address(target)?;
+                start..start + 1
+            };
+
+            if range.start >= range.end {
+                return Err(format!("{:?} is an empty range", target));
+            }
+            if range.end > size {
+                return Err(format!("{:?} reaches address {}, past the end of memory ({} words)", target, range.end - 1, size));
+            }
+            patches.push(Patch { range, value: resolve(value, symbols)? });
+        }
+        Ok(Patches(patches))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn apply(&self, machine: &mut Machine) -> Result<(), Error> {
+        for patch in &self.0 {
+            for address in patch.range.clone() {
+                machine.poke(address, patch.value)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The resolved spec, with symbols replaced by addresses.
+impl fmt::Display for Patches {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, patch) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, ",")?;
+            }
+            if patch.range.len() == 1 {
+                write!(f, "{}={}", patch.range.start, patch.value)?;
+            } else {
+                write!(f, "{}..{}={}", patch.range.start, patch.range.end, patch.value)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_day2() {
+        let program = vec![1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50];
+        let patches = Patches::parse("1=12, 2=2", &Symbols::new(), program.len()).unwrap();
+        assert_eq!(patches.to_string(), "1=12,2=2");
+
+        let mut machine = Machine::new(program);
+        patches.apply(&mut machine).unwrap();
+        assert_eq!(&machine.memory()[..3], &[1, 12, 2]);
+    }
+
+    #[test]
+    fn test_ranges_and_symbols() {
+        let symbols = Symbols::parse("# from the assembler\nnoun 1\nverb = 2\ntable 20\n").unwrap();
+        let patches = Patches::parse("noun=12,verb=table,table..table+3=0,table+4..=25=-1", &symbols, 30).unwrap();
+        assert_eq!(patches.to_string(), "1=12,2=20,20..23=0,24..26=-1");
+
+        let mut machine = Machine::new(vec![7; 30]);
+        patches.apply(&mut machine).unwrap();
+        assert_eq!(&machine.memory()[19..27], &[7, 0, 0, 0, 7, -1, -1, 7]);
+    }
+
+    #[test]
+    fn test_errors() {
+        let symbols = Symbols::parse("start 0\nnext 1").unwrap();
+        let parse = |spec| Patches::parse(spec, &symbols, 10).unwrap_err();
+        assert_eq!(parse("10=1"), "\"10\" reaches address 10, past the end of memory (10 words)");
+        assert_eq!(parse("5..=10=1"), "\"5..=10\" reaches address 10, past the end of memory (10 words)");
+        assert_eq!(parse("5..5=1"), "\"5..5\" is an empty range");
+        assert_eq!(parse("start-1=0"), "\"start-1\" is a negative address");
+        assert_eq!(parse("end=0"), "unknown symbol \"end\"");
+        assert_eq!(parse("1"), "expected TARGET=VALUE, not \"1\"");
+        assert_eq!(parse("1=x y"), "\"x y\" is neither a number nor a symbol");
+        assert_eq!(parse("next+9223372036854775807=0"), "\"next+9223372036854775807\" is out of range");
+        assert_eq!(parse("1=next+9223372036854775807"), "\"next+9223372036854775807\" is out of range");
+
+        assert_eq!(Symbols::parse("a 1\n2 b").unwrap_err(), "line 2: expected a name and an address, not \"2 b\"");
+    }
+}
@@ -0,0 +1,191 @@
+//! Execution profiles.
+//!
+//! With profiling switched on the machine counts every instruction it
+//! executes, by opcode and by address, every memory read and write by
+//! address, and every input and output. Cycles are a rough cost model: one
+//! per word of the instruction plus one per memory access made through a
+//! position or relative parameter.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::instruction::Opcode;
+
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Profile {
+    pub instructions: u64,
+    pub cycles: u64,
+    pub opcodes: HashMap<Opcode, u64>,
+    /// Executions by instruction address.
+    pub pcs: HashMap<usize, u64>,
+    pub reads: HashMap<usize, u64>,
+    pub writes: HashMap<usize, u64>,
+    pub inputs: u64,
+    pub outputs: u64,
+}
+
+
+/// Intensity ramp for the heatmaps, from untouched to hottest.
+const RAMP: &[u8] = b" .:-=+*#%@";
+
+
+fn top(counts: &HashMap<usize, u64>, n: usize) -> Vec<(usize, u64)> {
+    let mut counts: Vec<(usize, u64)> = counts.iter().map(|(&address, &count)| (address, count)).collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    counts.truncate(n);
+    counts
+}
+
+
+impl Profile {
+    pub(crate) fn on_instruction(&mut self, pc: usize, opcode: Opcode) {
+        self.instructions += 1;
+        self.cycles += opcode.arity() as u64 + 1;
+        *self.opcodes.entry(opcode).or_insert(0) += 1;
+        *self.pcs.entry(pc).or_insert(0) += 1;
+        match opcode {
+            Opcode::Input => self.inputs += 1,
+            Opcode::Output => self.outputs += 1,
+            _ => {}
+        }
+    }
+
+    pub(crate) fn on_read(&mut self, address: usize) {
+        self.cycles += 1;
+        *self.reads.entry(address).or_insert(0) += 1;
+    }
+
+    pub(crate) fn on_write(&mut self, address: usize) {
+        self.cycles += 1;
+        *self.writes.entry(address).or_insert(0) += 1;
+    }
+
+    /// Opcodes by how often they ran, most frequent first.
+    pub fn opcode_counts(&self) -> Vec<(Opcode, u64)> {
+        let mut counts: Vec<(Opcode, u64)> = self.opcodes.iter().map(|(&opcode, &count)| (opcode, count)).collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.mnemonic().cmp(b.0.mnemonic())));
+        counts
+    }
+
+    /// The `n` most executed instruction addresses.
+    pub fn hot_pcs(&self, n: usize) -> Vec<(usize, u64)> {
+        top(&self.pcs, n)
+    }
+
+    /// One character per address, `width` addresses to a row, scaled to the
+    /// most accessed address.
+    pub fn heatmap(counts: &HashMap<usize, u64>, width: usize) -> String {
+        let size = counts.keys().max().map_or(0, |&address| address + 1);
+        let hottest = counts.values().max().copied().unwrap_or(0);
+        let mut map = String::new();
+
+        for row in (0..size).step_by(width) {
+            write!(map, "{:>6} |", row).unwrap();
+            for address in row..(row + width).min(size) {
+                let count = counts.get(&address).copied().unwrap_or(0);
+                // round up, so that anything touched at all shows up
+                let level = (count as usize * (RAMP.len() - 1)).div_ceil(hottest.max(1) as usize);
+                map.push(RAMP[level] as char);
+            }
+            map.push_str("|\n");
+        }
+        map
+    }
+
+    /// A plain text report with the `n` hottest addresses.
+    pub fn table(&self, n: usize) -> String {
+        let mut text = String::new();
+        writeln!(text, "instructions  {:>12}", self.instructions).unwrap();
+        writeln!(text, "cycles        {:>12}", self.cycles).unwrap();
+        writeln!(text, "inputs        {:>12}", self.inputs).unwrap();
+        writeln!(text, "outputs       {:>12}", self.outputs).unwrap();
+
+        writeln!(text, "\nopcode             count       share").unwrap();
+        for (opcode, count) in self.opcode_counts() {
+            let share = 100.0 * count as f64 / self.instructions.max(1) as f64;
+            writeln!(text, "{:<8} {:>14} {:>10.1}%", opcode.mnemonic(), count, share).unwrap();
+        }
+
+        writeln!(text, "\naddress            count").unwrap();
+        for (pc, count) in self.hot_pcs(n) {
+            writeln!(text, "{:<8} {:>14}", pc, count).unwrap();
+        }
+
+        writeln!(text, "\nreads").unwrap();
+        text.push_str(&Profile::heatmap(&self.reads, 64));
+        writeln!(text, "\nwrites").unwrap();
+        text.push_str(&Profile::heatmap(&self.writes, 64));
+        text
+    }
+
+    /// The same report as JSON. Address keys are strings, as JSON requires.
+    pub fn to_json(&self, n: usize) -> String {
+        let counts = |counts: &mut dyn Iterator<Item = (String, u64)>| {
+            let entries: Vec<String> = counts.map(|(key, count)| format!("\"{}\": {}", key, count)).collect();
+            format!("{{{}}}", entries.join(", "))
+        };
+        let sorted = |map: &HashMap<usize, u64>| {
+            let mut entries: Vec<(usize, u64)> = map.iter().map(|(&a, &c)| (a, c)).collect();
+            entries.sort_unstable();
+            entries
+        };
+
+        format!(
+            "{{\"instructions\": {}, \"cycles\": {}, \"inputs\": {}, \"outputs\": {}, \"opcodes\": {}, \"hot\": [{}], \"reads\": {}, \"writes\": {}}}",
+            self.instructions,
+            self.cycles,
+            self.inputs,
+            self.outputs,
+            counts(&mut self.opcode_counts().into_iter().map(|(op, c)| (op.mnemonic().to_string(), c))),
+            self.hot_pcs(n).iter().map(|(pc, c)| format!("[{}, {}]", pc, c)).collect::<Vec<_>>().join(", "),
+            counts(&mut sorted(&self.reads).into_iter().map(|(a, c)| (a.to_string(), c))),
+            counts(&mut sorted(&self.writes).into_iter().map(|(a, c)| (a.to_string(), c))),
+        )
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Machine;
+
+    #[test]
+    fn test_profile() {
+        // day5's "is the input 8" program
+        let mut machine = Machine::new(vec![3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8]);
+        machine.enable_profiling();
+        machine.push_input(8);
+        machine.run().unwrap();
+
+        let profile = machine.profile().unwrap();
+        assert_eq!(profile.instructions, 4);
+        assert_eq!(profile.inputs, 1);
+        assert_eq!(profile.outputs, 1);
+        assert_eq!(profile.opcodes[&Opcode::Equals], 1);
+        assert_eq!(profile.reads.get(&9), Some(&2));
+        assert_eq!(profile.reads.get(&10), Some(&1));
+        assert_eq!(profile.writes.get(&9), Some(&2));
+        // 2 + 4 + 2 + 1 words, 3 reads and 2 writes
+        assert_eq!(profile.cycles, 14);
+
+        let json = profile.to_json(3);
+        assert!(json.starts_with("{\"instructions\": 4, \"cycles\": 14, \"inputs\": 1, \"outputs\": 1"));
+        assert!(json.contains("\"writes\": {\"9\": 2}"));
+        assert!(profile.table(3).contains("eq"));
+    }
+
+    #[test]
+    fn test_loop_counts() {
+        // count down from 3: the loop body runs three times
+        let mut machine = Machine::new(vec![1101, 3, 0, 20, 1006, 20, 15, 1001, 20, -1, 20, 1105, 1, 4, 0, 99]);
+        machine.enable_profiling();
+        machine.run().unwrap();
+
+        let profile = machine.profile().unwrap();
+        assert_eq!(profile.hot_pcs(1), vec![(4, 4)]);
+        assert_eq!(profile.opcodes[&Opcode::JumpIfTrue], 3);
+        assert_eq!(Profile::heatmap(&profile.writes, 16), "     0 |                |\n    16 |    @|\n");
+    }
+}
@@ -0,0 +1,231 @@
+//! A deliberately simple reference interpreter, used to check `Machine`.
+//!
+//! This follows the puzzle text as literally as possible: no decoding cache,
+//! no tracking, just read the instruction, pull the modes out digit by digit
+//! and do what it says. `compare` runs a program on both and reports the
+//! first difference, which is what the property tests and the fuzz target
+//! (`fuzz/fuzz_targets/differential.rs`) are built on.
+
+use crate::{Error, Machine, Status, Word, MAX_MEMORY};
+
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Outcome {
+    /// `Ok(Status::Running)` if the step limit ran out first.
+    pub result: Result<Status, Error>,
+    pub output: Vec<Word>,
+    pub memory: Vec<Word>,
+    pub pc: usize,
+}
+
+
+pub fn run(program: &[Word], input: &[Word], max_steps: usize) -> Outcome {
+    let mut memory = program.to_vec();
+    let mut input = input.iter();
+    let mut output = Vec::new();
+    let mut pc = 0;
+    let mut base: Word = 0;
+
+    let result = 'run: {
+        for _ in 0..max_steps {
+            let instruction = memory.get(pc).copied().unwrap_or(0);
+            let opcode = instruction % 100;
+            let count = match opcode {
+                1 | 2 | 7 | 8 => 3,
+                5 | 6 => 2,
+                3 | 4 | 9 => 1,
+                99 => 0,
+                _ => break 'run Err(Error::UnknownOpcode { pc, opcode }),
+            };
+
+            // (mode, raw parameter) for each parameter
+            let mut params = Vec::new();
+            for i in 0..count {
+                let mode = instruction / [100, 1000, 10000][i] % 10;
+                if mode > 2 {
+                    break 'run Err(Error::UnknownMode { pc, mode });
+                }
+                params.push((mode, memory.get(pc + 1 + i).copied().unwrap_or(0)));
+            }
+
+            let check = |address: Word| {
+                if address < 0 || address as usize >= MAX_MEMORY {
+                    Err(Error::InvalidAddress { pc, address })
+                } else {
+                    Ok(address as usize)
+                }
+            };
+            let read = |memory: &Vec<Word>, (mode, raw): (Word, Word)| -> Result<Word, Error> {
+                match mode {
+                    1 => Ok(raw),
+                    0 => Ok(memory.get(check(raw)?).copied().unwrap_or(0)),
+                    _ => Ok(memory.get(check(base.wrapping_add(raw))?).copied().unwrap_or(0)),
+                }
+            };
+            let write = |memory: &mut Vec<Word>, (mode, raw): (Word, Word), value: Word| -> Result<(), Error> {
+                let address = match mode {
+                    1 => return Err(Error::ImmediateWrite { pc }),
+                    0 => check(raw)?,
+                    _ => check(base.wrapping_add(raw))?,
+                };
+                if address >= memory.len() {
+                    memory.resize(address + 1, 0);
+                }
+                memory[address] = value;
+                Ok(())
+            };
+
+            let step = match opcode {
+                1 | 2 | 7 | 8 => read(&memory, params[0]).and_then(|a| {
+                    let b = read(&memory, params[1])?;
+                    let value = match opcode {
+                        1 => a.wrapping_add(b),
+                        2 => a.wrapping_mul(b),
+                        7 => (a < b) as Word,
+                        _ => (a == b) as Word,
+                    };
+                    write(&mut memory, params[2], value)?;
+                    Ok(pc + 4)
+                }),
+                3 => match input.next() {
+                    Some(&value) => write(&mut memory, params[0], value).map(|_| pc + 2),
+                    None => break 'run Ok(Status::NeedsInput),
+                },
+                4 => read(&memory, params[0]).map(|value| {
+                    output.push(value);
+                    pc + 2
+                }),
+                5 | 6 => read(&memory, params[0]).and_then(|condition| {
+                    if (condition != 0) == (opcode == 5) {
+                        check(read(&memory, params[1])?)
+                    } else {
+                        Ok(pc + 3)
+                    }
+                }),
+                9 => read(&memory, params[0]).map(|value| {
+                    base = base.wrapping_add(value);
+                    pc + 2
+                }),
+                _ => break 'run Ok(Status::Halted),
+            };
+
+            match step {
+                Ok(next) => pc = next,
+                Err(err) => break 'run Err(err),
+            }
+        }
+        Ok(Status::Running)
+    };
+
+    Outcome { result, output, memory, pc }
+}
+
+
+/// Run `program` on the reference interpreter and on `Machine`, and describe
+/// the first way in which they disagree.
+pub fn compare(program: &[Word], input: &[Word], max_steps: usize) -> Result<(), String> {
+    let expected = run(program, input, max_steps);
+
+    // check both a cold machine and one with every instruction decoded up front
+    for precompiled in [false, true] {
+        let mut machine = Machine::new(program.to_vec());
+        if precompiled {
+            machine.precompile();
+        }
+        for &value in input {
+            machine.push_input(value);
+        }
+        let mut result = Ok(Status::Running);
+        for _ in 0..max_steps {
+            result = machine.step();
+            if result != Ok(Status::Running) {
+                break;
+            }
+        }
+
+        let actual = Outcome {
+            result,
+            output: machine.drain_output(),
+            memory: machine.memory().to_vec(),
+            pc: machine.pc(),
+        };
+        let which = if precompiled { "precompiled machine" } else { "machine" };
+
+        if actual.result != expected.result {
+            return Err(format!("result: {} {:?}, reference {:?}", which, actual.result, expected.result));
+        }
+        if actual.output != expected.output {
+            return Err(format!("output: {} {:?}, reference {:?}", which, actual.output, expected.output));
+        }
+        if actual.pc != expected.pc {
+            return Err(format!("pc: {} {}, reference {}", which, actual.pc, expected.pc));
+        }
+        if actual.memory != expected.memory {
+            let address = actual.memory.iter().zip(&expected.memory).position(|(a, b)| a != b)
+                .unwrap_or_else(|| actual.memory.len().min(expected.memory.len()));
+            return Err(format!("memory: {} differs from the reference at address {}", which, address));
+        }
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_day5_examples() {
+        let equal_to_8 = [3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8];
+        assert_eq!(run(&equal_to_8, &[8], 100).output, vec![1]);
+        assert_eq!(run(&equal_to_8, &[1], 100).output, vec![0]);
+
+        let jump = [3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9];
+        assert_eq!(run(&jump, &[0], 100).output, vec![0]);
+        assert_eq!(run(&jump, &[10], 100).output, vec![1]);
+
+        for program in [&equal_to_8[..], &jump[..]] {
+            for value in -2..10 {
+                compare(program, &[value], 100).unwrap();
+            }
+        }
+    }
+
+    /// One instruction: an opcode (sometimes an invalid one), three mode
+    /// digits and parameters mostly pointing back into the program.
+    fn instruction(size: Word) -> impl Strategy<Value = Vec<Word>> {
+        let opcode = prop_oneof![
+            20 => 1..=9 as Word,
+            1 => Just(99 as Word),
+            1 => -5..120 as Word,
+        ];
+        let modes = proptest::collection::vec(prop_oneof![10 => 0..=2 as Word, 1 => 3..=9 as Word], 3);
+        let params = proptest::collection::vec(prop_oneof![4 => 0..size, 1 => -3..size * 2], 3);
+        (opcode, modes, params).prop_map(|(opcode, modes, params)| {
+            let mut words = vec![opcode + modes[0] * 100 + modes[1] * 1000 + modes[2] * 10000];
+            words.extend(params);
+            words
+        })
+    }
+
+    fn program() -> impl Strategy<Value = Vec<Word>> {
+        proptest::collection::vec(instruction(48), 1..12).prop_map(|instructions| {
+            let mut program: Vec<Word> = instructions.into_iter().flatten().collect();
+            program.push(99);
+            program
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn test_machine_matches_reference(
+            program in program(),
+            input in proptest::collection::vec(-100..100 as Word, 0..4),
+        ) {
+            if let Err(difference) = compare(&program, &input, 500) {
+                return Err(TestCaseError::fail(format!("{:?} on {:?}: {}", program, input, difference)));
+            }
+        }
+    }
+}
@@ -0,0 +1,166 @@
+//! Tracking of programs that write into their own instruction stream.
+//!
+//! Day2's programs already do this (`1,0,0,3` stores its result into one of
+//! its own parameters), and later puzzle inputs patch jump targets and
+//! opcodes on the fly. With tracking switched on the machine remembers which
+//! words have been executed as part of an instruction and which have been
+//! written by the program, and records every place the two overlap.
+
+use std::collections::HashMap;
+use std::fmt;
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CodeWriteKind {
+    /// A word that was already executed as part of an instruction was overwritten.
+    ExecutedCode,
+    /// A word written by the program was later executed as part of an instruction.
+    BeforeExecution,
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CodeWrite {
+    pub kind: CodeWriteKind,
+    /// Address of the instruction that did the writing.
+    pub writer: usize,
+    /// The word that was written.
+    pub address: usize,
+    /// Address of the instruction the word belongs to. For `ExecutedCode` this
+    /// is the last instruction that executed it.
+    pub instruction: usize,
+}
+
+impl fmt::Display for CodeWrite {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            CodeWriteKind::ExecutedCode => write!(
+                f, "{} overwrote {}, part of the already executed instruction at {}",
+                self.writer, self.address, self.instruction
+            ),
+            CodeWriteKind::BeforeExecution => write!(
+                f, "{} wrote {}, later executed as part of the instruction at {}",
+                self.writer, self.address, self.instruction
+            ),
+        }
+    }
+}
+
+
+#[derive(Debug, Clone, Default)]
+pub struct SelfModification {
+    /// Refuse to run instead of recording.
+    pub strict: bool,
+    pub events: Vec<CodeWrite>,
+    /// address -> start of the last instruction that executed it
+    executed: HashMap<usize, usize>,
+    /// address -> the instruction that last wrote it, until it is executed
+    written: HashMap<usize, usize>,
+}
+
+impl SelfModification {
+    pub fn new(strict: bool) -> SelfModification {
+        SelfModification { strict, ..SelfModification::default() }
+    }
+
+    pub fn is_executed(&self, address: usize) -> bool {
+        self.executed.contains_key(&address)
+    }
+
+    /// Called before the instruction at `writer` stores to `address`.
+    /// Returns the event that a strict tracker refuses.
+    pub(crate) fn on_write(&mut self, writer: usize, address: usize) -> Option<CodeWrite> {
+        if let Some(&instruction) = self.executed.get(&address) {
+            let event = CodeWrite { kind: CodeWriteKind::ExecutedCode, writer, address, instruction };
+            if self.strict {
+                return Some(event);
+            }
+            self.events.push(event);
+        }
+        self.written.insert(address, writer);
+        None
+    }
+
+    /// Called before the instruction spanning `start..start + size` executes.
+    pub(crate) fn on_execute(&mut self, start: usize, size: usize) -> Option<CodeWrite> {
+        for address in start..start + size {
+            if let Some(&writer) = self.written.get(&address) {
+                let event = CodeWrite { kind: CodeWriteKind::BeforeExecution, writer, address, instruction: start };
+                if self.strict {
+                    return Some(event);
+                }
+                self.events.push(event);
+                self.written.remove(&address);
+            }
+        }
+        for address in start..start + size {
+            self.executed.insert(address, start);
+        }
+        None
+    }
+
+    pub fn count(&self, kind: CodeWriteKind) -> usize {
+        self.events.iter().filter(|event| event.kind == kind).count()
+    }
+
+    /// A human readable summary of everything recorded so far.
+    pub fn report(&self) -> String {
+        let mut modified: Vec<usize> = self.events.iter().map(|event| event.address).collect();
+        modified.sort_unstable();
+        modified.dedup();
+
+        let mut writers: Vec<usize> = self.events.iter().map(|event| event.writer).collect();
+        writers.sort_unstable();
+        writers.dedup();
+
+        let mut text = format!(
+            "words executed: {}\noverwrites of executed code: {}\nwrites executed later: {}\nmodified addresses: {:?}\nwriting instructions: {:?}\n",
+            self.executed.len(),
+            self.count(CodeWriteKind::ExecutedCode),
+            self.count(CodeWriteKind::BeforeExecution),
+            modified,
+            writers,
+        );
+        for event in &self.events {
+            text.push_str(&format!("  {}\n", event));
+        }
+        text
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Error, Machine};
+
+    #[test]
+    fn test_day2_example() {
+        // 1,1,1,4,99,5,6,0,99: the add writes the 2 at address 4 that then runs as a multiply,
+        // which in turn overwrites the add at address 0
+        let mut machine = Machine::new(vec![1, 1, 1, 4, 99, 5, 6, 0, 99]);
+        machine.track_self_modification(false);
+        machine.run().unwrap();
+
+        let tracker = machine.self_modification().unwrap();
+        assert_eq!(tracker.events, vec![
+            CodeWrite { kind: CodeWriteKind::BeforeExecution, writer: 0, address: 4, instruction: 4 },
+            CodeWrite { kind: CodeWriteKind::ExecutedCode, writer: 4, address: 0, instruction: 0 },
+        ]);
+        assert!(tracker.report().contains("modified addresses: [0, 4]"));
+    }
+
+    #[test]
+    fn test_strict() {
+        let mut machine = Machine::new(vec![1, 1, 1, 4, 99, 5, 6, 0, 99]);
+        machine.track_self_modification(true);
+        assert_eq!(machine.run(), Err(Error::SelfModifying { pc: 4, address: 4 }));
+        // the add went through, the multiply it generated was refused
+        assert_eq!(machine.peek(4), 2);
+
+        let mut machine = Machine::new(vec![1101, 1, 1, 5, 99, 0]);
+        machine.track_self_modification(true);
+        machine.run().unwrap();
+        assert!(machine.self_modification().unwrap().events.is_empty());
+    }
+}
@@ -0,0 +1,74 @@
+//! Runs the `intcode` command on the puzzle inputs and checks what it prints
+//! and how it exits.
+
+use std::process::{Command, Stdio};
+
+
+/// Run `intcode` from the crate directory, returning the exit code, stdout
+/// and stderr.
+fn intcode(args: &[&str]) -> (i32, String, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_intcode"))
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+    let text = |bytes: &[u8]| String::from_utf8_lossy(bytes).into_owned();
+    (output.status.code().unwrap(), text(&output.stdout), text(&output.stderr))
+}
+
+
+#[test]
+fn test_run() {
+    // the diagnostic outputs a zero for every passing test, then the code
+    let (code, stdout, _) = intcode(&["run", "../day5/input.txt", "--input", "1"]);
+    assert_eq!(code, 0);
+    let outputs: Vec<&str> = stdout.lines().collect();
+    assert_eq!(outputs.last(), Some(&"7286649"));
+    assert!(outputs[..outputs.len() - 1].iter().all(|&line| line == "0"), "{}", stdout);
+}
+
+
+#[test]
+fn test_patch() {
+    let (code, stdout, _) = intcode(&["patch", "../day2/input.txt", "--set", "1=12,2=2"]);
+    assert_eq!((code, stdout.as_str()), (0, "3706713\n"));
+}
+
+
+#[test]
+fn test_report() {
+    let (_, _, stderr) = intcode(&["patch", "../day2/input.txt", "--set", "1=12", "--patch", "2=2", "--report"]);
+    assert!(stderr.contains("\npatches       2=2,1=12\n"), "{}", stderr);
+
+    let (_, _, stderr) = intcode(&["run", "../day5/input.txt", "--input", "5", "--report"]);
+    assert!(stderr.contains("\ninputs        5\n"), "{}", stderr);
+    assert!(!stderr.contains("patches"), "{}", stderr);
+}
+
+
+#[test]
+fn test_search() {
+    let (code, stdout, _) = intcode(&["search", "../day2/input.txt"]);
+    assert_eq!((code, stdout.as_str()), (0, "noun 86 verb 9: 8609\n"));
+}
+
+
+#[test]
+fn test_exit_codes() {
+    let (code, _, stderr) = intcode(&["run", "missing.txt"]);
+    assert_eq!(code, 5);
+    assert!(stderr.starts_with("failed to load missing.txt"), "{}", stderr);
+
+    let (code, _, _) = intcode(&["run", "../day5/input.txt"]);
+    assert_eq!(code, 3);
+
+    let (code, _, _) = intcode(&["run", "../day5/input.txt", "--input", "1", "--max-steps", "3"]);
+    assert_eq!(code, 4);
+
+    let (code, _, _) = intcode(&["search", "../day2/input.txt", "--target", "1", "--max", "1"]);
+    assert_eq!(code, 6);
+
+    let (code, _, _) = intcode(&["patch", "../day2/input.txt", "--set", "1=x"]);
+    assert_eq!(code, 2);
+}
@@ -0,0 +1,132 @@
+//! Runs every program in `tests/conformance`.
+//!
+//! Each `name.intcode` has a sibling `name.toml` with one or more cases:
+//!
+//! ```toml
+//! description = "what the program checks"
+//!
+//! [[case]]
+//! input = [8]           # queued before the run, defaults to none
+//! output = [1]          # everything output, defaults to none
+//! memory = [...]        # the whole of memory afterwards, optional
+//! status = "halted"     # or "needs-input", the default is "halted"
+//! error = "UnknownMode" # expect this kind of error instead of a status
+//! ```
+//!
+//! Every case is also run on the reference interpreter, so a fixture with a
+//! wrong expectation fails there as well.
+
+use std::fs::{read_dir, read_to_string};
+use std::path::Path;
+
+use intcode::reference;
+use intcode::{parse_program, Machine, Status, Word};
+use serde::Deserialize;
+
+const MAX_STEPS: usize = 100_000;
+
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Fixture {
+    #[allow(dead_code)]
+    description: String,
+    case: Vec<Case>,
+}
+
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Case {
+    #[allow(dead_code)]
+    description: Option<String>,
+    #[serde(default)]
+    input: Vec<Word>,
+    #[serde(default)]
+    output: Vec<Word>,
+    memory: Option<Vec<Word>>,
+    status: Option<String>,
+    error: Option<String>,
+}
+
+
+/// What happened, in the same terms the fixtures use.
+fn describe(result: &Result<Status, intcode::Error>) -> String {
+    match result {
+        Ok(Status::Running) => format!("still running after {} steps", MAX_STEPS),
+        Ok(status) => status.name().to_string(),
+        Err(err) => err.kind().to_string(),
+    }
+}
+
+
+fn check(name: &str, index: usize, program: &[Word], case: &Case) -> Vec<String> {
+    let mut failures = Vec::new();
+    let expected = match (&case.error, &case.status) {
+        (Some(error), _) => error.clone(),
+        (None, Some(status)) => status.clone(),
+        (None, None) => String::from("halted"),
+    };
+
+    let mut machine = Machine::new(program.to_vec());
+    for &value in &case.input {
+        machine.push_input(value);
+    }
+    let mut result = Ok(Status::Running);
+    for _ in 0..MAX_STEPS {
+        result = machine.step();
+        if result != Ok(Status::Running) {
+            break;
+        }
+    }
+    let output = machine.drain_output();
+
+    let mut fail = |what: String| failures.push(format!("{} case {}: {}", name, index, what));
+    if describe(&result) != expected {
+        fail(format!("expected {}, got {:?}", expected, result));
+    }
+    if output != case.output {
+        fail(format!("expected output {:?}, got {:?}", case.output, output));
+    }
+    if let Some(memory) = &case.memory {
+        if machine.memory() != &memory[..] {
+            fail(format!("expected memory {:?}, got {:?}", memory, machine.memory()));
+        }
+    }
+
+    let outcome = reference::run(program, &case.input, MAX_STEPS);
+    if describe(&outcome.result) != expected || outcome.output != case.output {
+        fail(format!("the reference interpreter disagrees: {:?} with output {:?}", outcome.result, outcome.output));
+    }
+
+    failures
+}
+
+
+#[test]
+fn test_conformance() {
+    let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/conformance");
+    let mut paths: Vec<_> = read_dir(&directory).unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "intcode"))
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty(), "no fixtures in {}", directory.display());
+
+    let mut failures = Vec::new();
+    let mut cases = 0;
+    for path in &paths {
+        let name = path.file_stem().unwrap().to_string_lossy();
+        let program = parse_program(&read_to_string(path).unwrap())
+            .unwrap_or_else(|err| panic!("{}: {}", name, err));
+        let fixture: Fixture = toml::from_str(&read_to_string(path.with_extension("toml")).unwrap())
+            .unwrap_or_else(|err| panic!("{}.toml: {}", name, err));
+
+        for (index, case) in fixture.case.iter().enumerate() {
+            cases += 1;
+            failures.extend(check(&name, index, &program, case));
+        }
+    }
+
+    assert!(failures.is_empty(), "{} of {} cases failed:\n{}", failures.len(), cases, failures.join("\n"));
+}
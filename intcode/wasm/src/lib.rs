@@ -0,0 +1,103 @@
+//! JavaScript bindings for the Intcode machine.
+//!
+//! Build with `wasm-pack build --target web` (or `--target nodejs`) and use it
+//! like this:
+//!
+//! ```js
+//! const machine = new Intcode("3,9,8,9,10,9,4,9,99,-1,8");
+//! machine.pushInput(8n);
+//! machine.run();              // "halted"
+//! machine.drainOutput();      // BigInt64Array [1n]
+//! ```
+//!
+//! Words are `i64`, so they cross into JavaScript as `BigInt`. There is no
+//! wall clock on `wasm32-unknown-unknown`, so runs are limited by steps
+//! rather than by a deadline.
+
+use intcode::{parse_program, Machine, Word};
+use wasm_bindgen::prelude::*;
+
+
+#[wasm_bindgen]
+pub struct Intcode {
+    machine: Machine,
+}
+
+#[wasm_bindgen]
+impl Intcode {
+    /// Load a program from its comma separated source.
+    #[wasm_bindgen(constructor)]
+    pub fn new(source: &str) -> Result<Intcode, JsError> {
+        let program = parse_program(source).map_err(|err| JsError::new(&err))?;
+        Ok(Intcode { machine: Machine::new(program) })
+    }
+
+    /// Execute one instruction.
+    pub fn step(&mut self) -> Result<String, JsError> {
+        match self.machine.step() {
+            Ok(status) => Ok(status.name().to_string()),
+            Err(err) => Err(JsError::new(&err.to_string())),
+        }
+    }
+
+    /// Run until the program halts or needs input, or for at most
+    /// `max_steps` instructions if given.
+    pub fn run(&mut self, max_steps: Option<u32>) -> Result<String, JsError> {
+        self.machine.set_fuel(max_steps.map(u64::from));
+        let result = self.machine.run();
+        self.machine.set_fuel(None);
+        match result {
+            Ok(status) => Ok(status.name().to_string()),
+            Err(err) => Err(JsError::new(&err.to_string())),
+        }
+    }
+
+    #[wasm_bindgen(js_name = pushInput)]
+    pub fn push_input(&mut self, value: Word) {
+        self.machine.push_input(value);
+    }
+
+    /// Queue a line of ASCII input, adding the newline.
+    #[wasm_bindgen(js_name = pushLine)]
+    pub fn push_line(&mut self, line: &str) {
+        self.machine.push_line(line);
+    }
+
+    #[wasm_bindgen(js_name = drainOutput)]
+    pub fn drain_output(&mut self) -> Vec<Word> {
+        self.machine.drain_output()
+    }
+
+    #[wasm_bindgen(js_name = takeAscii)]
+    pub fn take_ascii(&mut self) -> String {
+        self.machine.take_ascii()
+    }
+
+    pub fn peek(&self, address: usize) -> Word {
+        self.machine.peek(address)
+    }
+
+    pub fn poke(&mut self, address: usize, value: Word) -> Result<(), JsError> {
+        self.machine.poke(address, value).map_err(|err| JsError::new(&err.to_string()))
+    }
+
+    /// A copy of the whole of memory.
+    pub fn memory(&self) -> Vec<Word> {
+        self.machine.memory().to_vec()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn pc(&self) -> usize {
+        self.machine.pc()
+    }
+
+    #[wasm_bindgen(getter, js_name = relativeBase)]
+    pub fn relative_base(&self) -> Word {
+        self.machine.relative_base()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn halted(&self) -> bool {
+        self.machine.is_halted()
+    }
+}
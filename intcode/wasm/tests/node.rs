@@ -0,0 +1,67 @@
+//! Run with `wasm-pack test --node`.
+
+#![cfg(target_arch = "wasm32")]
+
+use intcode_wasm::Intcode;
+use wasm_bindgen_test::*;
+
+
+fn outputs(source: &str, input: i64) -> Vec<i64> {
+    let mut machine = Intcode::new(source).unwrap();
+    machine.push_input(input);
+    assert_eq!(machine.run(None).unwrap(), "halted");
+    machine.drain_output()
+}
+
+
+#[wasm_bindgen_test]
+fn test_day5_examples() {
+    let equal_to_8 = "3,9,8,9,10,9,4,9,99,-1,8";
+    assert_eq!(outputs(equal_to_8, 8), vec![1]);
+    assert_eq!(outputs(equal_to_8, 5), vec![0]);
+
+    let less_than_8 = "3,3,1107,-1,8,3,4,3,99";
+    assert_eq!(outputs(less_than_8, 7), vec![1]);
+    assert_eq!(outputs(less_than_8, 8), vec![0]);
+
+    let jump = "3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9";
+    assert_eq!(outputs(jump, 0), vec![0]);
+    assert_eq!(outputs(jump, 3), vec![1]);
+}
+
+
+#[wasm_bindgen_test]
+fn test_stepping_and_memory() {
+    let mut machine = Intcode::new("1,9,10,3,2,3,11,0,99,30,40,50").unwrap();
+    assert_eq!(machine.step().unwrap(), "running");
+    assert_eq!(machine.pc(), 4);
+    assert_eq!(machine.peek(3), 70);
+
+    assert_eq!(machine.run(None).unwrap(), "halted");
+    assert!(machine.halted());
+    assert_eq!(machine.memory(), vec![3500, 9, 10, 70, 2, 3, 11, 0, 99, 30, 40, 50]);
+}
+
+
+#[wasm_bindgen_test]
+fn test_pauses() {
+    // echoes its input, then jumps to itself forever
+    let mut machine = Intcode::new("3,7,4,7,1105,1,4,0").unwrap();
+    assert_eq!(machine.run(None).unwrap(), "needs-input");
+    machine.push_input(7);
+    assert_eq!(machine.run(Some(10)).unwrap(), "budget-exhausted");
+    assert_eq!(machine.drain_output(), vec![7]);
+
+    assert_eq!(machine.pc(), 4);
+    machine.poke(4, 104).unwrap();
+    assert_eq!(machine.step().unwrap(), "running");
+    assert_eq!(machine.drain_output(), vec![1]);
+}
+
+
+#[wasm_bindgen_test]
+fn test_poke_out_of_range() {
+    let mut machine = Intcode::new("99").unwrap();
+    assert!(machine.poke(1 << 30, 1).is_err());
+    assert_eq!(machine.memory(), vec![99]);
+}